@@ -0,0 +1,21 @@
+# termila shell integration for bash (4.4 or newer)
+# marks prompts, commands and their outputs (OSC 133) and reports working directory (OSC 7)
+# usage: add `source /path/to/termila.bash` at the end of ~/.bashrc
+
+if [[ $- == *i* && -z $__termila_integration ]]; then
+	__termila_integration=1
+
+	__termila_precmd() {
+		local status=$?
+		# exit status of the last command (ignored by termila when no command was run)
+		printf '\e]133;D;%s\a' "$status"
+		local path=${PWD//%/%25}
+		printf '\e]7;file://%s%s\a' "$HOSTNAME" "${path// /%20}"
+		# prompt can be replaced by other PROMPT_COMMAND, so marks are added back each time
+		[[ $PS1 == *'133;A'* ]] || PS1='\[\e]133;A\a\]'$PS1'\[\e]133;B\a\]'
+		[[ $PS0 == *'133;C'* ]] || PS0+='\e]133;C\a'
+		return $status
+	}
+
+	PROMPT_COMMAND="__termila_precmd${PROMPT_COMMAND:+; $PROMPT_COMMAND}"
+fi
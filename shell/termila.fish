@@ -0,0 +1,26 @@
+# termila shell integration for fish (3.0 or newer)
+# marks prompts, commands and their outputs (OSC 133) and reports working directory (OSC 7)
+# usage: add `source /path/to/termila.fish` at the end of ~/.config/fish/config.fish
+
+if status is-interactive; and not set -q __termila_integration
+	set -g __termila_integration 1
+
+	function __termila_prompt --on-event fish_prompt
+		printf '\e]7;file://%s%s\a\e]133;A\a' $hostname (string escape --style=url -- $PWD)
+	end
+
+	function __termila_preexec --on-event fish_preexec
+		printf '\e]133;C\a'
+	end
+
+	function __termila_postexec --on-event fish_postexec
+		printf '\e]133;D;%s\a' $status
+	end
+
+	# command line starts where the prompt ends
+	functions -c fish_prompt __termila_original_prompt
+	function fish_prompt
+		__termila_original_prompt
+		printf '\e]133;B\a'
+	end
+fi
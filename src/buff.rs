@@ -0,0 +1,1521 @@
+// terminal buffer (screen, scrollback, cursor and styles) and its html rendering
+// (doesnt need webview - rendering produces js that UI runs)
+
+use crate::parser::PARSER_handler;
+use crate::keys::KEYS_modes;
+use std::collections::{HashMap, VecDeque};
+use std::cmp::min;
+use std::time::{Duration, Instant};
+
+
+pub enum BUFF_clipboard { // clipboard request from application (OSC 52), done by UI
+	Set(String), // copy text
+	Query(String), // send clipboard content back (value is the selection parameter to be used in the reply)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BUFF_color {
+	Default, // terminal default (foreground or background depending on where it is used)
+	Indexed(u8), // xterm 256-color palette index
+	Rgb(u8,u8,u8), // truecolor
+}
+impl BUFF_color {
+
+	pub fn to_css(&self) -> Option<String> {
+		// indexed colors come from the palette (css variables set by UI, see PALETTE::to_css)
+		match *self {
+			BUFF_color::Default => None,
+			BUFF_color::Indexed(idx) => Some(format!("var(--termila_color_{})", idx)),
+			BUFF_color::Rgb(r, g, b) => Some(format!("rgb({},{},{})", r, g, b)),
+		}
+	}
+
+	#[allow(dead_code)] // used by tests
+	pub fn describe(&self) -> String {
+		match *self {
+			BUFF_color::Default => "default".to_string(),
+			BUFF_color::Indexed(idx) => idx.to_string(),
+			BUFF_color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+		}
+	}
+
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct BUFF_style {
+	pub fg: BUFF_color, // text color
+	pub bg: BUFF_color, // background color
+	pub underline_color: BUFF_color, // default means same as text
+	pub bold: bool,
+	pub dim: bool,
+	pub italic: bool,
+	pub underline: u8, // 0 none, 1 single, 2 double, 3 curly, 4 dotted, 5 dashed (same as sgr 4:x)
+	pub blink: bool,
+	pub inverse: bool,
+	pub hidden: bool,
+	pub strikethrough: bool,
+	pub overline: bool,
+	pub link: u32, // OSC 8 hyperlink (index into BUFF::links + 1, 0 means none)
+}
+impl BUFF_style {
+
+	pub const DEFAULT: BUFF_style = BUFF_style {
+		fg: BUFF_color::Default,
+		bg: BUFF_color::Default,
+		underline_color: BUFF_color::Default,
+		bold: false,
+		dim: false,
+		italic: false,
+		underline: 0,
+		blink: false,
+		inverse: false,
+		hidden: false,
+		strikethrough: false,
+		overline: false,
+		link: 0,
+	};
+
+	pub fn to_css(&self) -> String {
+		let mut css = String::new();
+
+		// colors (inverse swaps them, including the defaults)
+		let mut fg = self.fg.to_css();
+		let mut bg = self.bg.to_css();
+		if self.inverse {
+			(fg, bg) = (
+				Some(bg.unwrap_or("var(--termila_bg)".to_string())),
+				Some(fg.unwrap_or("var(--termila_fg)".to_string())),
+			);
+		}
+		if self.hidden { fg = Some("transparent".to_string()); }
+		else if self.dim { fg = Some(format!("color-mix(in srgb, {} 50%, transparent)", fg.unwrap_or("var(--termila_fg)".to_string()))); }
+		if let Some(color) = fg { css.push_str(&format!("color: {};", color)); }
+		if let Some(color) = bg { css.push_str(&format!("background-color: {};", color)); }
+
+		// font
+		if self.bold { css.push_str("font-weight: bold;"); }
+		if self.italic { css.push_str("font-style: italic;"); }
+
+		// lines
+		let mut lines = vec![];
+		if self.underline != 0 { lines.push("underline"); }
+		if self.strikethrough { lines.push("line-through"); }
+		if self.overline { lines.push("overline"); }
+		if !lines.is_empty() {
+			css.push_str(&format!("text-decoration-line: {};", lines.join(" ")));
+			match self.underline {
+				2 => css.push_str("text-decoration-style: double;"),
+				3 => css.push_str("text-decoration-style: wavy;"),
+				4 => css.push_str("text-decoration-style: dotted;"),
+				5 => css.push_str("text-decoration-style: dashed;"),
+				_ => {}
+			}
+			if let Some(color) = self.underline_color.to_css() { css.push_str(&format!("text-decoration-color: {};", color)); }
+		}
+
+		if self.blink { css.push_str("animation: termila_blink 1s step-end infinite;"); }
+
+		return css;
+	}
+
+	#[allow(dead_code)] // used by tests
+	pub fn describe(&self) -> String { // non-default attributes as text (ie. 'fg=1 bold underline=3')
+		let mut attributes = vec![];
+		if self.fg != BUFF_color::Default { attributes.push(format!("fg={}", self.fg.describe())); }
+		if self.bg != BUFF_color::Default { attributes.push(format!("bg={}", self.bg.describe())); }
+		if self.underline_color != BUFF_color::Default { attributes.push(format!("underline_color={}", self.underline_color.describe())); }
+		for (enabled, name) in [(self.bold, "bold"), (self.dim, "dim"), (self.italic, "italic"), (self.blink, "blink"), (self.inverse, "inverse"), (self.hidden, "hidden"), (self.strikethrough, "strikethrough"), (self.overline, "overline")] {
+			if enabled { attributes.push(name.to_string()); }
+		}
+		match self.underline {
+			0 => {}
+			1 => attributes.push("underline".to_string()),
+			u => attributes.push(format!("underline={}", u)),
+		}
+		if self.link != 0 { attributes.push(format!("link={}", self.link)); }
+		return attributes.join(" ");
+	}
+
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct BUFF_cell {
+	pub chr: char, // displayed character (blank cells hold a space)
+	pub style: BUFF_style, // attributes of this cell
+}
+
+#[derive(Clone, Default, PartialEq)]
+pub struct BUFF_mark { // shell integration marks of a line (OSC 133), set on the line where the cursor was when the shell sent them
+	pub prompt: bool, // A, prompt starts on this line
+	pub command: Option<usize>, // B, command line starts at this column (end of prompt)
+	pub output: bool, // C, command output starts on this line
+	pub exit: Option<i32>, // D, exit status of the command (kept on its prompt line)
+	pub duration: Option<Duration>, // time from C to D (on the prompt line, not described as it differs each run)
+	pub cwd: String, // working directory reported by shell (OSC 7) when the prompt was shown (on prompt lines)
+}
+impl BUFF_mark {
+
+	#[allow(dead_code)] // used by tests
+	pub fn describe(&self) -> String { // set marks as text (ie. 'prompt command=3 cwd=/home')
+		let mut parts = vec![];
+		if self.prompt { parts.push("prompt".to_string()); }
+		if let Some(column) = self.command { parts.push(format!("command={}", column+1)); }
+		if self.output { parts.push("output".to_string()); }
+		if let Some(exit) = self.exit { parts.push(format!("exit={}", exit)); }
+		if !self.cwd.is_empty() { parts.push(format!("cwd={}", self.cwd)); }
+		return parts.join(" ");
+	}
+
+}
+
+pub struct BUFF_line {
+	pub cells: Vec<BUFF_cell>, // one cell per column (scrollback lines keep the width they had when they scrolled off)
+	pub updated: bool, // changed but not displayed
+	pub id: usize, // html id, 0 means not in DOM yet, set when update runs, '#l-<value>'
+	pub mark: BUFF_mark, // where prompts and outputs of commands begin
+	pub block: usize, // command block the line belongs to (number of the prompt above it, 0 when there was none)
+}
+
+pub struct BUFF {
+	pub screen: Vec<BUFF_line>, // visible rows, always exactly size_rows lines of size_columns cells
+	pub scrollback: VecDeque<BUFF_line>, // lines scrolled off the top of the screen, oldest first
+	pub scrollback_max: usize, // max number of lines kept in scrollback
+	pub scrollback_pending: usize, // number of scrollback lines that are new or updated but not displayed
+	pub removed_ids: Vec<usize>, // html ids of lines dropped from the buffer, removed from DOM on next update
+	pub last_id: usize,
+
+	pub cursor_row: usize, // 0-based, from the top of the screen
+	pub cursor_column: usize, // 0-based, from the left
+	pub cursor_wrap: bool, // last column was just written, next printable character goes to the next line first
+	pub saved_cursor: (usize, usize, BUFF_style), // DECSC/DECRC and CSI s/u (row, column, style)
+	pub cursor_visible: bool, // DECTCEM
+	pub cursor_shape: usize, // DECSCUSR (1 blinking block, 2 block, 3 blinking underline, 4 underline, 5 blinking bar, 6 bar), 0 means cursor_default
+	pub cursor_default: usize, // shape from options (same numbers)
+	pub cursor_displayed: Option<(usize, usize)>, // line id and column of the cursor in DOM
+	pub cursor_shape_displayed: usize, // shape in DOM
+	pub style: BUFF_style, // style applied to newly written characters
+
+	pub scroll_top: usize, // first row of the scrolling region (DECSTBM), 0-based
+	pub scroll_bottom: usize, // last row of the scrolling region (inclusive), 0-based
+	pub origin_mode: bool, // DECOM, cursor addressing is relative to the scrolling region
+	pub key_modes: KEYS_modes, // how keys are encoded for the application (DECCKM, DECKPAM, kitty keyboard flags, modifyOtherKeys, bracketed paste, mouse and focus reporting)
+	pub kitty_stack: Vec<u8>, // kitty keyboard flags pushed before the current ones
+	pub kitty_saved: (u8, Vec<u8>), // kitty keyboard flags and stack of the primary screen while alternate is active (each screen has its own)
+
+	pub alternate: bool, // alternate screen is active (the primary one is kept in saved_screen)
+	pub alternate_displayed: bool, // state of alternate screen in DOM
+	pub mouse_displayed: usize, // mouse tracking mode in DOM (the page reports mouse events only while it is set)
+	pub synchronized: Option<Instant>, // synchronized output (DECSET 2026) start, DOM updates wait until the application finishes its frame
+	pub saved_screen: Vec<BUFF_line>, // the inactive screen (primary while alternate is active, empty otherwise)
+
+	pub size_rows: usize,
+	pub size_columns: usize,
+
+	pub console: String, // html id of the element the buffer is displayed in
+
+	pub responses: Vec<u8>, // replies to queries (device attributes, cursor position, ...), PANE writes them to the pty
+
+	pub title: String, // window title (OSC 0/2), empty when not set
+	pub icon_name: String, // icon name (OSC 0/1), not displayed anywhere
+	pub title_stack: Vec<(Option<String>, Option<String>)>, // saved (icon name, title) pairs (XTWINOPS 22/23), None when only the other one was saved
+
+	pub links: Vec<String>, // uris of OSC 8 hyperlinks (cells refer to them by BUFF_style::link, empty ones were forgotten)
+	pub link_ids: HashMap<String, u32>, // the same uri gets the same id
+	pub links_collect_at: usize, // size of links when unused ones are forgotten next time
+
+	pub clipboard: Vec<BUFF_clipboard>, // OSC 52 requests waiting for UI
+
+	pub cwd: String, // working directory reported by shell (OSC 7), empty when not reported
+	pub cwd_host: String, // host the working directory is on (empty or 'localhost' for this machine)
+	pub command_output: bool, // command was started (OSC 133 C) since the last prompt, so its exit status belongs to it
+	pub command_start: Option<Instant>, // when the running command was started (OSC 133 C)
+	pub block: usize, // number of the last prompt, lines the cursor gets to belong to its command block
+}
+impl BUFF {
+
+	pub const MAX_TITLE_STACK: usize = 10; // same as xterm
+	pub const MAX_KITTY_STACK: usize = 16; // oldest entries are dropped
+	pub const MIN_LINKS_COLLECT: usize = 256; // link table is cleaned once it has this many uris (or twice as many as were used at the last cleaning)
+	pub const SYNC_TIMEOUT: Duration = Duration::from_millis(500); // frame is shown anyway when synchronized output isnt ended in time
+
+	pub fn new(rows: usize, columns: usize, scrollback_max: usize) -> Self {
+		let rows = rows.max(1);
+		let columns = columns.max(1);
+		let mut this = Self {
+			screen: vec![],
+			scrollback: VecDeque::new(),
+			scrollback_max,
+			scrollback_pending: 0,
+			removed_ids: vec![],
+			last_id: 0,
+			cursor_row: 0,
+			cursor_column: 0,
+			cursor_wrap: false,
+			saved_cursor: (0, 0, BUFF_style::DEFAULT),
+			cursor_visible: true,
+			cursor_shape: 0,
+			cursor_default: 1,
+			cursor_displayed: None,
+			cursor_shape_displayed: 0,
+			style: BUFF_style::DEFAULT,
+			scroll_top: 0,
+			scroll_bottom: rows-1,
+			origin_mode: false,
+			key_modes: KEYS_modes::default(),
+			kitty_stack: vec![],
+			kitty_saved: (0, vec![]),
+			alternate: false,
+			alternate_displayed: false,
+			mouse_displayed: 0,
+			synchronized: None,
+			saved_screen: vec![],
+			size_rows: rows,
+			size_columns: columns,
+			console: "console".to_string(),
+			responses: vec![],
+			title: String::new(),
+			icon_name: String::new(),
+			title_stack: vec![],
+			links: vec![],
+			link_ids: HashMap::new(),
+			links_collect_at: BUFF::MIN_LINKS_COLLECT,
+			clipboard: vec![],
+			cwd: String::new(),
+			cwd_host: String::new(),
+			command_output: false,
+			command_start: None,
+			block: 0,
+		};
+		for _ in 0..rows { let line = this.new_line(); this.screen.push(line); }
+		this
+	}
+
+	pub fn blank(&self) -> BUFF_cell {
+		// erased cells keep the current background (like xterm does)
+		BUFF_cell { chr: ' ', style: BUFF_style { bg: self.style.bg, ..BUFF_style::DEFAULT } }
+	}
+
+	pub fn new_line(&self) -> BUFF_line {
+		BUFF_line { cells: vec![self.blank(); self.size_columns], updated: true, id: 0, mark: BUFF_mark::default(), block: 0 }
+	}
+
+	pub fn drop_line(&mut self, line: BUFF_line) {
+		if line.id != 0 { self.removed_ids.push(line.id); }
+	}
+
+	pub fn push_scrollback(&mut self, line: BUFF_line) {
+		if line.id == 0 || line.updated { self.scrollback_pending += 1; }
+		self.scrollback.push_back(line);
+
+		// clear old scrollback to save memory
+		while self.scrollback.len() > self.scrollback_max {
+			let old = self.scrollback.pop_front().unwrap();
+			if old.id == 0 || old.updated { self.scrollback_pending -= 1; }
+			self.drop_line(old);
+		}
+	}
+
+	pub fn scroll_out(&mut self, line: BUFF_line) {
+		// line scrolled off the top of the screen (alternate screen has no scrollback)
+		if self.alternate { self.drop_line(line); }
+		else { self.push_scrollback(line); }
+	}
+
+	pub fn resize(&mut self, rows: usize, columns: usize) {
+		let rows = rows.max(1);
+		let columns = columns.max(1);
+		if rows == self.size_rows && columns == self.size_columns { return; }
+
+		eprintln!("(info)  BUFF: resize {}x{} -> {}x{}", self.size_columns, self.size_rows, columns, rows);
+
+		// columns (no reflow, lines are just cut or extended)
+		if columns != self.size_columns {
+			self.size_columns = columns;
+			let blank = BUFF_cell { chr: ' ', style: BUFF_style::DEFAULT };
+			for line in self.screen.iter_mut().chain(self.saved_screen.iter_mut()) {
+				line.cells.resize(columns, blank);
+				line.updated = true;
+			}
+		}
+
+		// inactive primary screen is shrunk like the active one (lines above its saved cursor go to scrollback, so it comes back with the prompt) or extended at the bottom
+		if self.alternate {
+			let to_scrollback = (self.saved_cursor.0 + 1).saturating_sub(rows).min(self.saved_screen.len().saturating_sub(rows));
+			for _ in 0..to_scrollback {
+				let line = self.saved_screen.remove(0);
+				self.push_scrollback(line);
+			}
+			self.saved_cursor.0 -= to_scrollback;
+			while self.saved_screen.len() > rows {
+				let line = self.saved_screen.pop().unwrap();
+				self.drop_line(line);
+			}
+			while self.saved_screen.len() < rows {
+				let line = BUFF_line { cells: vec![BUFF_cell { chr: ' ', style: BUFF_style::DEFAULT }; columns], updated: true, id: 0, mark: BUFF_mark::default(), block: 0 };
+				self.saved_screen.push(line);
+			}
+		}
+
+		// rows (when shrinking, lines above the cursor go to scrollback and empty lines below it are dropped)
+		if rows < self.size_rows {
+			let to_scrollback = (self.cursor_row + 1).saturating_sub(rows);
+			for _ in 0..to_scrollback {
+				let line = self.screen.remove(0);
+				self.scroll_out(line);
+			}
+			while self.screen.len() > rows {
+				let line = self.screen.pop().unwrap();
+				self.drop_line(line);
+			}
+			self.cursor_row -= to_scrollback;
+			if !self.alternate { self.saved_cursor.0 = self.saved_cursor.0.saturating_sub(to_scrollback); } // primary cursor was moved with its screen above
+		}
+		self.size_rows = rows;
+		while self.screen.len() < rows {
+			let line = self.new_line();
+			self.screen.push(line);
+		}
+
+		// scrolling region is reset to the whole screen
+		self.scroll_top = 0;
+		self.scroll_bottom = rows-1;
+
+		// keep cursors inside the screen
+		self.cursor_row = min(self.cursor_row, rows-1);
+		self.cursor_column = min(self.cursor_column, columns-1);
+		self.cursor_wrap = false;
+		self.saved_cursor.0 = min(self.saved_cursor.0, rows-1);
+		self.saved_cursor.1 = min(self.saved_cursor.1, columns-1);
+	}
+
+
+	pub fn write_buff(&mut self, chr: char) {
+
+		// finish pending wrap from previous character
+		if self.cursor_wrap {
+			self.cursor_wrap = false;
+			self.cursor_column = 0;
+			self.linefeed();
+		}
+
+		// place character
+		let style = self.style;
+		let line = &mut self.screen[self.cursor_row];
+		line.cells[self.cursor_column] = BUFF_cell { chr, style };
+		line.updated = true;
+		self.mark_block();
+
+		// advance cursor (at the last column, the wrap is delayed until next character)
+		if self.cursor_column + 1 < self.size_columns { self.cursor_column += 1; }
+		else { self.cursor_wrap = true; }
+
+	}
+
+	pub fn linefeed(&mut self) {
+		// at the bottom margin the region scrolls, below it the cursor just stops at the last row
+		if self.cursor_row == self.scroll_bottom { self.scroll_up(1); }
+		else if self.cursor_row + 1 < self.size_rows { self.cursor_row += 1; }
+		self.mark_block();
+	}
+
+	pub fn mark_block(&mut self) { // line under the cursor belongs to the current command block (full screen programs have none)
+		if self.alternate { return; }
+		let block = self.block;
+		let line = &mut self.screen[self.cursor_row];
+		if line.block != block {
+			line.block = block;
+			line.updated = true;
+		}
+	}
+
+	pub fn reverse_index(&mut self) {
+		// at the top margin the region scrolls down, above it the cursor just stops at the first row
+		if self.cursor_row == self.scroll_top { self.scroll_down(1); }
+		else if self.cursor_row > 0 { self.cursor_row -= 1; }
+	}
+
+	pub fn scroll_up(&mut self, n: usize) { // content of scrolling region moves up, new lines at the bottom
+		self.scroll_lines_up(self.scroll_top, self.scroll_bottom, n, true);
+	}
+
+	pub fn scroll_down(&mut self, n: usize) { // content of scrolling region moves down, new lines at the top
+		self.scroll_lines_down(self.scroll_top, self.scroll_bottom, n);
+	}
+
+	pub fn scroll_lines_up(&mut self, top: usize, bottom: usize, n: usize, to_scrollback: bool) {
+		// only lines leaving the very top of the screen go to scrollback
+		for _ in 0..min(n, bottom+1-top) {
+			let line = self.screen.remove(top);
+			if to_scrollback && top == 0 { self.scroll_out(line); }
+			else { self.drop_line(line); }
+			let line = self.new_line();
+			self.screen.insert(bottom, line);
+		}
+	}
+
+	pub fn scroll_lines_down(&mut self, top: usize, bottom: usize, n: usize) {
+		for _ in 0..min(n, bottom+1-top) {
+			let line = self.screen.remove(bottom);
+			self.drop_line(line);
+			let line = self.new_line();
+			self.screen.insert(top, line);
+		}
+	}
+
+	pub fn insert_lines(&mut self, n: usize) { // IL, lines from cursor down are pushed toward the bottom margin
+		if self.cursor_row < self.scroll_top || self.cursor_row > self.scroll_bottom { return; }
+		self.scroll_lines_down(self.cursor_row, self.scroll_bottom, n);
+		self.set_cursor(self.cursor_row, 0);
+	}
+
+	pub fn delete_lines(&mut self, n: usize) { // DL, lines below cursor are pulled up, blank lines appear at the bottom margin
+		if self.cursor_row < self.scroll_top || self.cursor_row > self.scroll_bottom { return; }
+		self.scroll_lines_up(self.cursor_row, self.scroll_bottom, n, false);
+		self.set_cursor(self.cursor_row, 0);
+	}
+
+	pub fn erase_cells(&mut self, row: usize, from: usize, to: usize) { // erases [from, to) on given row
+		let blank = self.blank();
+		let line = &mut self.screen[row];
+		let to = min(to, line.cells.len());
+		if from >= to { return; }
+		for cell in &mut line.cells[from..to] { *cell = blank; }
+		line.updated = true;
+	}
+
+	pub fn erase_row(&mut self, row: usize) { // erases whole row with its shell marks (used by ED, EL keeps them as shells use it to redraw the prompt)
+		self.erase_cells(row, 0, self.size_columns);
+		let line = &mut self.screen[row];
+		if line.mark.prompt { self.command_output = false; } // prompt of the running command is gone (ie. 'clear'), its exit status has nowhere to go
+		line.mark = BUFF_mark::default();
+		line.block = 0;
+	}
+
+	pub fn mark_prompt_exit(&mut self, exit: Option<i32>, duration: Option<Duration>) { // exit status (and how long it took) goes to the last prompt line at or above the cursor
+		let row = self.cursor_row;
+		if let Some(line) = self.screen[..=row].iter_mut().rev().find(|line| line.mark.prompt) {
+			line.mark.exit = exit;
+			line.mark.duration = duration;
+			line.updated = true;
+		}
+		else if let Some(line) = self.scrollback.iter_mut().rev().find(|line| line.mark.prompt) {
+			if line.id != 0 && !line.updated { self.scrollback_pending += 1; }
+			line.mark.exit = exit;
+			line.mark.duration = duration;
+			line.updated = true;
+		}
+	}
+
+	pub fn set_cursor(&mut self, row: usize, column: usize) {
+		self.cursor_row = min(row, self.size_rows-1);
+		self.cursor_column = min(column, self.size_columns-1);
+		self.cursor_wrap = false;
+	}
+
+	pub fn clear_scrollback(&mut self) {
+		while let Some(line) = self.scrollback.pop_front() {
+			self.drop_line(line);
+		}
+		self.scrollback_pending = 0;
+	}
+
+	pub fn set_alternate(&mut self, enable: bool) {
+		if enable == self.alternate { return; }
+
+		// swap screens (primary lines stay in DOM, just hidden while alternate is active)
+		std::mem::swap(&mut self.screen, &mut self.saved_screen);
+		self.alternate = enable;
+
+		// keyboard flags belong to the screen (alternate one starts without them)
+		if enable { self.kitty_saved = (self.key_modes.kitty_flags, std::mem::take(&mut self.kitty_stack)); self.key_modes.kitty_flags = 0; }
+		else { (self.key_modes.kitty_flags, self.kitty_stack) = std::mem::take(&mut self.kitty_saved); }
+
+		if enable { // alternate screen always starts cleared
+			self.screen = (0..self.size_rows).map(|_| self.new_line()).collect();
+		}
+		else { // alternate screen is discarded
+			for line in std::mem::take(&mut self.saved_screen) { self.drop_line(line); }
+		}
+	}
+
+	pub fn set_mode(&mut self, mode: usize, enable: bool) {
+		match mode {
+
+			1 => { // application cursor keys (DECCKM)
+				self.key_modes.application_cursor = enable;
+			}
+
+			6 => { // origin mode (also moves cursor home)
+				self.origin_mode = enable;
+				let top = if enable { self.scroll_top } else { 0 };
+				self.set_cursor(top, 0);
+			}
+
+			25 => { // cursor visible (DECTCEM)
+				self.cursor_visible = enable;
+			}
+
+			47 | 1047 => { // alternate screen
+				self.set_alternate(enable);
+			}
+
+			66 => { // application keypad (DECNKM, same as DECKPAM/DECKPNM)
+				self.key_modes.application_keypad = enable;
+			}
+
+			9 | 1000 | 1002 | 1003 => { // mouse tracking (X10, normal, button-event, any-event), only one at a time
+				if enable { self.key_modes.mouse_tracking = mode; }
+				else if self.key_modes.mouse_tracking == mode { self.key_modes.mouse_tracking = 0; }
+			}
+
+			1004 => { // focus in/out reporting
+				self.key_modes.focus_reporting = enable;
+			}
+
+			1005 | 1006 | 1015 => { // mouse report encoding (utf-8, SGR, urxvt)
+				if enable { self.key_modes.mouse_encoding = mode; }
+				else if self.key_modes.mouse_encoding == mode { self.key_modes.mouse_encoding = 0; }
+			}
+
+			1048 => { // save/restore cursor
+				if enable { self.saved_cursor = (self.cursor_row, self.cursor_column, self.style); }
+				else { let (row, column, style) = self.saved_cursor; self.set_cursor(row, column); self.style = style; }
+			}
+
+			1049 => { // save cursor and switch to cleared alternate screen / switch back and restore cursor
+				if enable {
+					if self.alternate { return; } // already switched, saved primary cursor is kept
+					self.saved_cursor = (self.cursor_row, self.cursor_column, self.style);
+					self.set_alternate(true);
+				}
+				else {
+					self.set_alternate(false);
+					let (row, column, style) = self.saved_cursor;
+					self.set_cursor(row, column);
+					self.style = style;
+				}
+			}
+
+			2004 => { // bracketed paste
+				self.key_modes.bracketed_paste = enable;
+			}
+
+			2026 => { // synchronized output (begin and end of frame)
+				if !enable { self.synchronized = None; }
+				else if self.synchronized.is_none() { self.synchronized = Some(Instant::now()); }
+			}
+
+			_ => {
+				eprintln!("(info)  BUFF: unsupported mode {} {}", mode, if enable {"enabled"} else {"disabled"});
+			}
+		}
+	}
+
+	pub fn get_mode(&self, mode: usize) -> usize { // state of dec private mode for DECRQM (0 not recognized, 1 set, 2 reset, 3 permanently set, 4 permanently reset)
+		let state = |enabled: bool| if enabled { 1 } else { 2 };
+		match mode {
+			1 => state(self.key_modes.application_cursor),
+			6 => state(self.origin_mode),
+			25 => state(self.cursor_visible),
+			66 => state(self.key_modes.application_keypad),
+			7 => 3, // autowrap is always on
+			47 | 1047 | 1049 => state(self.alternate),
+			9 | 1000 | 1002 | 1003 => state(self.key_modes.mouse_tracking == mode),
+			1004 => state(self.key_modes.focus_reporting),
+			1005 | 1006 | 1015 => state(self.key_modes.mouse_encoding == mode),
+			2004 => state(self.key_modes.bracketed_paste),
+			2026 => state(self.synchronized.is_some()),
+			_ => 0,
+		}
+	}
+
+	pub fn link_id(&mut self, uri: &str) -> u32 { // id of OSC 8 uri (ids of forgotten uris are reused)
+		if let Some(id) = self.link_ids.get(uri) { return *id; }
+		if self.links.len() >= self.links_collect_at { self.collect_links(); }
+		let index = match self.links.iter().position(|link| link.is_empty()) {
+			Some(free) => { self.links[free] = uri.to_string(); free }
+			None => { self.links.push(uri.to_string()); self.links.len() - 1 }
+		};
+		self.link_ids.insert(uri.to_string(), index as u32 + 1);
+		return index as u32 + 1;
+	}
+
+	pub fn collect_links(&mut self) { // forget uris no cell refers to anymore (lines with them left scrollback or were cleared)
+		let mut used = vec![false; self.links.len()];
+		let styles = self.scrollback.iter().chain(self.saved_screen.iter()).chain(self.screen.iter()).flat_map(|line| line.cells.iter().map(|cell| &cell.style));
+		for style in styles.chain([&self.style, &self.saved_cursor.2]) {
+			if let Some(used) = used.get_mut((style.link as usize).wrapping_sub(1)) { *used = true; }
+		}
+		for (link, used) in self.links.iter_mut().zip(&used) {
+			if !used && !link.is_empty() { self.link_ids.remove(link.as_str()); link.clear(); }
+		}
+		self.links_collect_at = (2 * used.iter().filter(|used| **used).count()).max(BUFF::MIN_LINKS_COLLECT);
+	}
+
+	pub fn cursor(&self) -> Option<(usize, usize)> { // line id and column of the visible cursor
+		if !self.cursor_visible { return None; }
+		return self.screen.get(self.cursor_row).map(|line| (line.id, self.cursor_column));
+	}
+
+	pub fn displayed_shape(&self) -> usize { // DECSCUSR shape with the default one resolved
+		return if self.cursor_shape == 0 { self.cursor_default } else { self.cursor_shape };
+	}
+
+	pub fn synchronizing(&self) -> bool { // application is drawing a frame (DOM update should wait)
+		return self.synchronized.is_some_and(|start| start.elapsed() < BUFF::SYNC_TIMEOUT);
+	}
+
+	pub fn respond(&mut self, response: &str) {
+		eprintln!("(info)  BUFF: response {:?}", response);
+		self.responses.extend_from_slice(response.as_bytes());
+	}
+
+	pub fn sgr(style: &mut BUFF_style, params: &[Vec<usize>]) { // apply graphic rendition codes to style
+
+		// each code can have ':' separated sub-parameters (ie. '4:3' or '38:2::255:0:0'), no parameters is equivalent to '0'
+
+		// extended color either from sub-parameters or from following codes
+		fn extended_color<'p>(sub: &[usize], iter: &mut dyn Iterator<Item = &'p Vec<usize>>) -> Option<BUFF_color> {
+			let mut next = |i: usize| -> Option<u8> {
+				if sub.len() > 1 { sub.get(i).and_then(|v| u8::try_from(*v).ok()) }
+				else { iter.next().and_then(|p| u8::try_from(p[0]).ok()) }
+			};
+			match next(1) {
+				Some(5) => next(2).map(BUFF_color::Indexed),
+				Some(2) => {
+					// the colon form may contain color space id before the values ('38:2:<id>:R:G:B')
+					let skip = if sub.len() >= 6 { 1 } else { 0 };
+					let r = next(2+skip).unwrap_or(0);
+					let g = next(3+skip).unwrap_or(0);
+					let b = next(4+skip).unwrap_or(0);
+					Some(BUFF_color::Rgb(r, g, b))
+				}
+				_ => None,
+			}
+		}
+
+		let mut iter = params.iter();
+
+		while let Some(code) = iter.next() {
+			match code[0] {
+
+				// Reset
+				0 => { *style = BUFF_style { link: style.link, ..BUFF_style::DEFAULT }; } // hyperlink is not graphic rendition
+
+				// Attributes
+				1 => { style.bold = true; }
+				2 => { style.dim = true; }
+				3 => { style.italic = true; }
+				4 => { style.underline = code.get(1).copied().unwrap_or(1).min(5) as u8; }
+				5 | 6 => { style.blink = true; }
+				7 => { style.inverse = true; }
+				8 => { style.hidden = true; }
+				9 => { style.strikethrough = true; }
+				21 => { style.underline = 2; }
+				53 => { style.overline = true; }
+
+				// Reset attributes
+				22 => { style.bold = false; style.dim = false; }
+				23 => { style.italic = false; }
+				24 => { style.underline = 0; }
+				25 => { style.blink = false; }
+				27 => { style.inverse = false; }
+				28 => { style.hidden = false; }
+				29 => { style.strikethrough = false; }
+				55 => { style.overline = false; }
+
+				// Foreground standard
+				c @ 30..=37 => { style.fg = BUFF_color::Indexed((c - 30) as u8); }
+
+				// Background standard
+				c @ 40..=47 => { style.bg = BUFF_color::Indexed((c - 40) as u8); }
+
+				// Foreground bright
+				c @ 90..=97 => { style.fg = BUFF_color::Indexed((c - 90 + 8) as u8); }
+
+				// Background bright
+				c @ 100..=107 => { style.bg = BUFF_color::Indexed((c - 100 + 8) as u8); }
+
+				// Reset fg/bg/underline color
+				39 => { style.fg = BUFF_color::Default; }
+				49 => { style.bg = BUFF_color::Default; }
+				59 => { style.underline_color = BUFF_color::Default; }
+
+				// 256-color: 38;5;n / 48;5;n / 58;5;n and TrueColor: 38;2;R;G;B / 48;2;R;G;B / 58;2;R;G;B
+				c @ (38 | 48 | 58) => {
+					let color = match extended_color(code, &mut iter) {
+						Some(color) => color,
+						None => continue,
+					};
+					match c {
+						38 => style.fg = color,
+						48 => style.bg = color,
+						_ => style.underline_color = color,
+					}
+				}
+
+				_ => {
+					// unknown or unsupported code, ignore
+				}
+			}
+		}
+	}
+
+
+	// scroll console 'c' to the bottom unless disabled in menu
+	pub const AUTOSCROLL: &'static str = "if (document.querySelector('#menu button#autoscroll').dataset.checked!='true') {c.scrollTop = c.scrollHeight;}";
+
+	pub fn render_element (console: &str, line: &BUFF_line, alternate: bool, links: &[String], cursor: Option<usize>) -> String { // line with its element, classes tell alternate screen lines and shell integration marks
+		let mut classes = vec![];
+		if alternate { classes.push("alternate"); }
+		if line.mark.prompt { classes.push("prompt"); }
+		if line.mark.output { classes.push("output"); }
+		let class = if classes.is_empty() { String::new() } else { format!(" class=\"{}\"", classes.join(" ")) };
+		let mut data = String::new();
+		if line.block != 0 { data.push_str(&format!(" data-block=\"{}\"", line.block)); }
+		if let Some(column) = line.mark.command { data.push_str(&format!(" data-command=\"{}\"", column)); }
+		if let Some(exit) = line.mark.exit { data.push_str(&format!(" data-exit=\"{}\"", exit)); }
+		if line.mark.exit.is_some() || line.mark.duration.is_some() { // finished command (shown by its prompt)
+			let mut info = vec![];
+			if let Some(exit) = line.mark.exit { info.push(format!("exit {}", exit)); }
+			if let Some(duration) = line.mark.duration { info.push(format_duration(duration)); }
+			data.push_str(&format!(" data-info=\"{}\"", info.join(", ")));
+		}
+		return format!("<span id=\"{}-{}\"{}{}>{}</span>", console, line.id, class, data, BUFF::render_line(line, links, cursor));
+	}
+
+	pub fn render_line (line: &BUFF_line, links: &[String], cursor: Option<usize>) -> String { // cursor column gets its own element (shaped by css)
+
+		// trailing blank cells are not displayed (so selection doesnt copy them), except the one under the cursor
+		let end = line.cells.iter().rposition(|cell| cell.chr != ' ' || cell.style != BUFF_style::DEFAULT).map_or(0, |i| i+1);
+		let end = cursor.map_or(end, |column| end.max(column + 1)).min(line.cells.len());
+
+		// group cells with the same style into spans
+		let mut html = String::new();
+		let mut start = 0;
+		while start < end {
+			let style = line.cells[start].style;
+			let mut text = String::new();
+			let mut i = start;
+			while i < end && line.cells[i].style == style && (i == start || cursor != Some(i)) {
+				text.push(line.cells[i].chr);
+				i += 1;
+				if cursor == Some(start) { break; }
+			}
+			let mut text = crate::UI::escape_text(&text);
+			if cursor == Some(start) { text = format!("<span class=\"cursor\">{}</span>", text); }
+			let mut span = if (BUFF_style { link: 0, ..style }) == BUFF_style::DEFAULT { text }
+				else { format!("<span style=\"{}\">{}</span>", style.to_css(), text) };
+			if let Some(uri) = links.get((style.link as usize).wrapping_sub(1)) { // uri is shown on hover and opened by id (see UI::open_link)
+				span = format!("<a data-link=\"{}\" title=\"{}\">{}</a>", style.link, crate::UI::escape_text(uri).replace('"', "&quot;"), span);
+			}
+			html.push_str(&span);
+			start = i;
+		}
+
+		return html;
+	}
+
+	pub fn update_full (&mut self) -> String { // full terminal update (slow), returns js to be run in the webview
+
+		// create html (primary screen is hidden behind the alternate one when it is active)
+		let mut html = String::new();
+		let screen_start = self.scrollback.len() + self.saved_screen.len();
+		let cursor_line = if self.cursor_visible { Some(screen_start + self.cursor_row) } else { None };
+		for (i, line) in self.scrollback.iter_mut().chain(self.saved_screen.iter_mut()).chain(self.screen.iter_mut()).enumerate() {
+			if line.id == 0 {
+				self.last_id += 1;
+				line.id = self.last_id;
+			}
+			let cursor = if cursor_line == Some(i) { Some(self.cursor_column) } else { None };
+			html.push_str(&BUFF::render_element(&self.console, line, self.alternate && i >= screen_start, &self.links, cursor));
+			line.updated = false;
+		}
+		self.cursor_displayed = self.cursor();
+		self.cursor_shape_displayed = self.displayed_shape();
+		self.scrollback_pending = 0;
+		self.removed_ids.clear();
+		self.alternate_displayed = self.alternate;
+		self.mouse_displayed = self.key_modes.mouse_tracking;
+
+		// update whole terminal content
+		return format!("(function(){{const c=document.getElementById('{}'); c.innerHTML=`{}`; c.classList.toggle('alternate', {}); c.dataset.mouse={}; c.dataset.cursor={}; {}}})()", self.console, html, self.alternate, self.mouse_displayed, self.cursor_shape_displayed, BUFF::AUTOSCROLL);
+
+	}
+
+	pub fn update_partial (&mut self) -> Option<String> { // partial terminal update (only changed lines), returns js to be run in the webview
+
+		let mut js_call = format!("(function(){{const c=document.getElementById('{}');", self.console); // store all js commands and then run them all at once (reduces calling overhead)
+		let mut changes = 0;
+
+		// remove dropped lines
+		for id in self.removed_ids.drain(..) {
+			js_call.push_str(&format!("document.getElementById('{}-{}').remove();", self.console, id));
+			changes += 1;
+		}
+
+		// show only alternate screen lines while it is active
+		if self.alternate != self.alternate_displayed {
+			js_call.push_str(&format!("c.classList.toggle('alternate', {});", self.alternate));
+			self.alternate_displayed = self.alternate;
+			changes += 1;
+		}
+
+		// page reports mouse events by tracking mode
+		if self.key_modes.mouse_tracking != self.mouse_displayed {
+			js_call.push_str(&format!("c.dataset.mouse={};", self.key_modes.mouse_tracking));
+			self.mouse_displayed = self.key_modes.mouse_tracking;
+			changes += 1;
+		}
+
+		// cursor shape is set by css, moved cursor needs its old and new line rendered again
+		if self.displayed_shape() != self.cursor_shape_displayed {
+			self.cursor_shape_displayed = self.displayed_shape();
+			js_call.push_str(&format!("c.dataset.cursor={};", self.cursor_shape_displayed));
+			changes += 1;
+		}
+		let cursor = self.cursor();
+		if cursor != self.cursor_displayed {
+			if let Some((id, _)) = self.cursor_displayed {
+				if let Some(line) = self.screen.iter_mut().find(|line| line.id == id) { line.updated = true; }
+				else if let Some(line) = self.scrollback.iter_mut().rev().find(|line| line.id == id) {
+					if line.id != 0 && !line.updated { self.scrollback_pending += 1; } // not counted yet
+					line.updated = true;
+				}
+				else if let Some(line) = self.saved_screen.iter_mut().find(|line| line.id == id) { line.updated = true; } // rendered once its screen is back
+			}
+			if cursor.is_some() { self.screen[self.cursor_row].updated = true; }
+		}
+
+		// add or edit lines (from the end, so new lines can be placed before the following one)
+		let mut next_id = 0;
+		let screen = self.screen.iter_mut().rev();
+		let scrollback = self.scrollback.iter_mut().rev();
+		let mut pending = self.scrollback_pending;
+		let cursor_line = if self.cursor_visible { Some(self.size_rows - 1 - self.cursor_row) } else { None }; // counted from the end
+		for (i, line) in screen.chain(scrollback).enumerate() {
+			let cursor = if cursor_line == Some(i) { Some(self.cursor_column) } else { None };
+
+			// stop in scrollback when all pending lines were processed
+			if i >= self.size_rows {
+				if pending == 0 { break; }
+				if line.id == 0 || line.updated { pending -= 1; }
+			}
+
+			if line.id == 0 { // add element
+				self.last_id += 1;
+				line.id = self.last_id;
+				js_call.push_str(
+					&format!(
+						"{}.insertAdjacentHTML('{}', `{}`);",
+						if next_id != 0 {format!("document.getElementById('{}-{}')", self.console, next_id)} else {"c".to_string()},
+						if next_id != 0 {"beforebegin"} else {"beforeend"},
+						BUFF::render_element(&self.console, line, self.alternate && i < self.size_rows, &self.links, cursor),
+					)
+				);
+				line.updated = false;
+				changes += 1;
+			}
+
+			else if line.updated { // edit element (replaced whole, marks may have changed)
+				js_call.push_str(
+					&format!(
+						"document.getElementById('{}-{}').outerHTML=`{}`;",
+						self.console,
+						line.id,
+						BUFF::render_element(&self.console, line, self.alternate && i < self.size_rows, &self.links, cursor),
+					)
+				);
+				line.updated = false;
+				changes += 1;
+			}
+
+			next_id = line.id;
+		}
+		self.scrollback_pending = 0;
+		self.cursor_displayed = self.cursor();
+
+		if changes == 0 { return None; } // nothing to update
+
+		js_call.push_str(BUFF::AUTOSCROLL);
+		js_call.push_str("})()");
+		return Some(js_call);
+
+	}
+
+	#[allow(dead_code)] // used by tests
+	pub fn line_text (line: &BUFF_line) -> String { // plain text of line without trailing spaces
+		let text: String = line.cells.iter().map(|cell| cell.chr).collect();
+		return text.trim_end_matches(' ').to_string();
+	}
+
+	#[allow(dead_code)] // used by tests
+	pub fn screen_text (&self) -> Vec<String> {
+		self.screen.iter().map(BUFF::line_text).collect()
+	}
+
+	// readable dump of the buffer state (text of scrollback and screen, styles of the screen and cursor), used by tests to compare with golden snapshots
+	#[allow(dead_code)] // used by tests
+	pub fn snapshot (&self) -> String {
+
+		let mut snapshot = format!("size: {}x{}\n", self.size_columns, self.size_rows);
+		snapshot.push_str(&format!("cursor: {},{}{}\n", self.cursor_row+1, self.cursor_column+1, if self.cursor_wrap {" (wrap pending)"} else {""}));
+		snapshot.push_str(&format!("scroll region: {}-{}{}\n", self.scroll_top+1, self.scroll_bottom+1, if self.origin_mode {" (origin mode)"} else {""}));
+		snapshot.push_str(&format!("screen: {}\n", if self.alternate {"alternate"} else {"primary"}));
+
+		snapshot.push_str(&format!("\nscrollback: {} lines\n", self.scrollback.len()));
+		for line in &self.scrollback {
+			snapshot.push_str(&format!("    |{}\n", BUFF::line_text(line)));
+		}
+
+		snapshot.push_str("\ntext:\n");
+		for (row, text) in self.screen_text().iter().enumerate() {
+			snapshot.push_str(&format!("{:>3} |{}\n", row+1, text));
+		}
+
+		// styled runs of cells (row, first and last column)
+		snapshot.push_str("\nstyles:\n");
+		for (row, line) in self.screen.iter().enumerate() {
+			let mut start = 0;
+			while start < line.cells.len() {
+				let style = line.cells[start].style;
+				let mut end = start;
+				while end+1 < line.cells.len() && line.cells[end+1].style == style { end += 1; }
+				if style != BUFF_style::DEFAULT {
+					snapshot.push_str(&format!("{:>3} |{}-{}: {}\n", row+1, start+1, end+1, style.describe()));
+				}
+				start = end+1;
+			}
+		}
+
+		// shell integration marks (scrollback lines are numbered from 0 upwards, so the oldest has the lowest number)
+		let marked: Vec<(isize, &BUFF_line)> = self.scrollback.iter().chain(self.screen.iter()).enumerate()
+			.map(|(i, line)| (i as isize - self.scrollback.len() as isize + 1, line))
+			.filter(|(_, line)| line.mark != BUFF_mark::default()).collect();
+		if !marked.is_empty() {
+			snapshot.push_str("\nmarks:\n");
+			for (row, line) in marked {
+				snapshot.push_str(&format!("{:>3} |{}\n", row, line.mark.describe()));
+			}
+		}
+
+		// command blocks (ranges of lines with the same block, numbered like marks)
+		let lines: Vec<&BUFF_line> = self.scrollback.iter().chain(self.screen.iter()).collect();
+		let first_row = 1 - self.scrollback.len() as isize;
+		let mut blocks = String::new();
+		let mut start = 0;
+		while start < lines.len() {
+			let mut end = start;
+			while end+1 < lines.len() && lines[end+1].block == lines[start].block { end += 1; }
+			if lines[start].block != 0 {
+				blocks.push_str(&format!("{:>3}-{} |{}\n", first_row + start as isize, first_row + end as isize, lines[start].block));
+			}
+			start = end+1;
+		}
+		if !blocks.is_empty() {
+			snapshot.push_str("\nblocks:\n");
+			snapshot.push_str(&blocks);
+		}
+
+		if !self.links.is_empty() {
+			snapshot.push_str("\nlinks:\n");
+			for (i, uri) in self.links.iter().enumerate() {
+				snapshot.push_str(&format!("{:>3} |{}\n", i+1, uri));
+			}
+		}
+
+		return snapshot;
+	}
+
+	/*
+	positioning specs:
+
+		terminal specification:
+			column: left to right, starts at 1, values over size are interpreted as max
+			row: top to bottom, starts at 1, values over size are interpreted as max
+
+		set position methods:
+			column: left to right, starts at 0, values over size are interpreted as max
+			row: top to bottom, starts at 0, values over size are interpreted as max (relative to the visible screen, scrollback is not addressable)
+
+		dom structure:
+			each line of scrollback and screen is one '<span id="<console>-<id>">' in '<p class="console" id="<console>">' (one per tab), in the same order as in the buffer
+			only lines marked as updated (or not yet displayed) are regenerated, lines dropped from the buffer are removed by id
+			(cursor position is the cell to be overwriten by the next character)
+
+	*/
+
+}
+
+
+impl PARSER_handler for BUFF {
+
+	// list of all common sequences here: https://xtermjs.org/docs/api/vtfeatures/
+
+	fn print(&mut self, chr: char) {
+		self.write_buff(chr);
+	}
+
+	fn execute(&mut self, byte: u8) {
+		match byte {
+			0x07 => { // bell
+				eprintln!("(info)  BUFF: bell !!!");
+				// TODO: audio
+			}
+			0x08 => { // backspace
+				// the cursour should move one character to the left, but its not supposed to delete it
+				if self.cursor_column > 0 { self.cursor_column -= 1; }
+				self.cursor_wrap = false;
+			}
+			b'\t' => { // horizontal tab (fixed tab stops every 8 columns)
+				let column = min((self.cursor_column / 8 + 1) * 8, self.size_columns-1);
+				self.set_cursor(self.cursor_row, column);
+			}
+			b'\n' | 0x0b | 0x0c => { // newline \n \v \f
+				// only moves down, the pty translates '\n' to '\r\n' when the application wants it
+				self.cursor_wrap = false;
+				self.linefeed();
+			}
+			b'\r' => { // carriage return
+				self.set_cursor(self.cursor_row, 0);
+			}
+			_ => {
+				// other control characters are just not displayed
+			}
+		}
+	}
+
+	fn esc_dispatch(&mut self, intermediates: &[u8], byte: u8) {
+		match (intermediates, byte) {
+			([], b'7') => { // save cursor
+				self.saved_cursor = (self.cursor_row, self.cursor_column, self.style);
+			}
+			([], b'8') => { // restore cursor
+				let (row, column, style) = self.saved_cursor;
+				self.set_cursor(row, column);
+				self.style = style;
+			}
+			([], b'D') => { // index
+				self.cursor_wrap = false;
+				self.linefeed();
+			}
+			([], b'E') => { // next line
+				self.set_cursor(self.cursor_row, 0);
+				self.linefeed();
+			}
+			([], b'M') => { // reverse index
+				self.cursor_wrap = false;
+				self.reverse_index();
+			}
+			([], b'=') => { // application keypad (DECKPAM)
+				self.key_modes.application_keypad = true;
+			}
+			([], b'>') => { // numeric keypad (DECKPNM)
+				self.key_modes.application_keypad = false;
+			}
+			([], b'\\') => { // string terminator (string itself was already handled)
+			}
+			// TODO: many more (character sets, reset, ...)
+			_ => { eprintln!("(info)  BUFF: unsupported sequence ESC {:?} {}", String::from_utf8_lossy(intermediates), byte as char); }
+		}
+	}
+
+	fn osc_dispatch(&mut self, data: &str) {
+		let (command, value) = data.split_once(';').unwrap_or((data, ""));
+		match command {
+			"0" => { // set icon name and title
+				self.icon_name = value.to_string();
+				self.title = value.to_string();
+			}
+			"1" => { // set icon name
+				self.icon_name = value.to_string();
+			}
+			"2" => { // set title
+				self.title = value.to_string();
+			}
+			"52" => { // clipboard ('52;<selection>;<base64 text>' copies, '52;<selection>;?' asks for the content - selection is ignored, there is only one clipboard)
+				let (selection, data) = value.split_once(';').unwrap_or((value, ""));
+				if data == "?" {
+					self.clipboard.push(BUFF_clipboard::Query(if selection.is_empty() { "c".to_string() } else { selection.to_string() }));
+				}
+				else {
+					match base64_decode(data) {
+						Some(text) => self.clipboard.push(BUFF_clipboard::Set(String::from_utf8_lossy(&text).to_string())),
+						None => { eprintln!("(info)  BUFF: invalid clipboard data"); }
+					}
+				}
+			}
+			"7" => { // working directory ('7;file://<host><path>', path is percent-encoded)
+				match value.strip_prefix("file://").and_then(|rest| rest.find('/').map(|i| rest.split_at(i))) {
+					Some((host, path)) => {
+						self.cwd_host = host.to_string();
+						self.cwd = percent_decode(path);
+					}
+					None => { eprintln!("(info)  BUFF: invalid working directory '{}'", value); }
+				}
+			}
+			"133" => { // shell integration ('133;A' prompt, '133;B' command line, '133;C' command output, '133;D;<exit status>' command finished, other parameters are ignored)
+				if self.alternate { return; } // full screen programs have no prompts (and shell in tmux cant be marked in tmux screen)
+				let mut params = value.split(';');
+				let row = self.cursor_row;
+				match params.next() {
+					Some("A") => { // starts new command block
+						self.block += 1;
+						let (cwd, block) = (self.cwd.clone(), self.block);
+						let line = &mut self.screen[row];
+						line.mark.prompt = true;
+						line.mark.cwd = cwd;
+						line.block = block;
+						line.updated = true;
+						self.command_output = false;
+					}
+					Some("B") => {
+						let column = self.cursor_column;
+						let line = &mut self.screen[row];
+						line.mark.command = Some(column);
+						line.updated = true;
+					}
+					Some("C") => {
+						let line = &mut self.screen[row];
+						line.mark.output = true;
+						line.updated = true;
+						self.command_output = true;
+						self.command_start = Some(Instant::now());
+					}
+					Some("D") => {
+						// shells send it before every prompt, even when no command was run
+						if self.command_output {
+							self.command_output = false;
+							let duration = self.command_start.take().map(|start| start.elapsed());
+							self.mark_prompt_exit(params.next().and_then(|exit| exit.parse::<i32>().ok()), duration);
+						}
+					}
+					_ => { eprintln!("(info)  BUFF: unsupported shell integration mark '{}'", value); }
+				}
+			}
+			"8" => { // hyperlink ('8;params;uri' starts it, empty uri ends it, params like 'id=' are not needed as cells store the link itself)
+				let uri = value.split_once(';').map_or("", |(_, uri)| uri);
+				self.style.link = if uri.is_empty() { 0 } else { self.link_id(uri) };
+			}
+			// TODO: many more
+			_ => { eprintln!("(info)  BUFF: unsupported sequence OSC {}", command); }
+		}
+	}
+
+	fn csi_dispatch(&mut self, params: &[Vec<usize>], intermediates: &[u8], byte: u8) {
+
+		let param = |i: usize| params.get(i).map_or(0, |p| p[0]);
+		let n = param(0).max(1); // most sequences take a count where 0 means 1
+
+		match (intermediates, byte) {
+
+			([], b'm') => { // graphic rendition (colors and attributes)
+				BUFF::sgr(&mut self.style, params);
+			}
+
+			([b'>'], b'm') if param(0) == 4 => { // modifyOtherKeys level (XTMODKEYS, missing value resets it)
+				self.key_modes.modify_other_keys = if params.len() > 1 { min(param(1), 2) as u8 } else { 0 };
+			}
+			([b'>'], b'n') if param(0) == 4 => { // modifyOtherKeys off
+				self.key_modes.modify_other_keys = 0;
+			}
+			([b'?'], b'm') if param(0) == 4 => { // modifyOtherKeys query (XTQMODKEYS)
+				self.respond(&format!("\x1b[>4;{}m", self.key_modes.modify_other_keys));
+			}
+
+			([b'>'], b'u') => { // kitty keyboard flags push
+				if self.kitty_stack.len() >= BUFF::MAX_KITTY_STACK { self.kitty_stack.remove(0); }
+				self.kitty_stack.push(self.key_modes.kitty_flags);
+				self.key_modes.kitty_flags = param(0) as u8 & KEYS_modes::KITTY_FLAGS;
+			}
+			([b'<'], b'u') => { // kitty keyboard flags pop (emptied stack resets them)
+				for _ in 0..min(n, BUFF::MAX_KITTY_STACK+1) {
+					self.key_modes.kitty_flags = self.kitty_stack.pop().unwrap_or(0);
+				}
+			}
+			([b'='], b'u') => { // kitty keyboard flags set (1 replace, 2 add, 3 remove)
+				let flags = param(0) as u8 & KEYS_modes::KITTY_FLAGS;
+				match param(1) {
+					0 | 1 => self.key_modes.kitty_flags = flags,
+					2 => self.key_modes.kitty_flags |= flags,
+					3 => self.key_modes.kitty_flags &= !flags,
+					_ => {}
+				}
+			}
+			([b'?'], b'u') => { // kitty keyboard flags query
+				self.respond(&format!("\x1b[?{}u", self.key_modes.kitty_flags));
+			}
+
+			([], b't') => { // window operations (only title stack is supported, the window is not controlled by applications)
+				// second parameter says what is saved or restored: 0 both, 1 icon name, 2 title
+				let icon = param(1) == 0 || param(1) == 1;
+				let title = param(1) == 0 || param(1) == 2;
+				match param(0) {
+					22 => { // push
+						if self.title_stack.len() >= BUFF::MAX_TITLE_STACK { self.title_stack.remove(0); }
+						self.title_stack.push((Some(self.icon_name.clone()).filter(|_| icon), Some(self.title.clone()).filter(|_| title)));
+					}
+					23 => { // pop
+						if let Some((saved_icon, saved_title)) = self.title_stack.pop() {
+							if let Some(saved_icon) = saved_icon.filter(|_| icon) { self.icon_name = saved_icon; }
+							if let Some(saved_title) = saved_title.filter(|_| title) { self.title = saved_title; }
+						}
+					}
+					_ => {}
+				}
+			}
+
+			([], b'r') => { // scrolling region
+				let top = param(0).max(1);
+				let bottom = match param(1) { 0 => self.size_rows, b => min(b, self.size_rows) };
+				if top < bottom { // region needs at least two lines
+					self.scroll_top = top-1;
+					self.scroll_bottom = bottom-1;
+					let home = if self.origin_mode { self.scroll_top } else { 0 };
+					self.set_cursor(home, 0);
+				}
+			}
+
+			([], b'S') => { // scroll up
+				self.scroll_up(n);
+			}
+
+			([], b'T') if params.len() == 1 => { // scroll down (with more parameters it is mouse highlight tracking)
+				self.scroll_down(n);
+			}
+
+			([], b'L') => { // insert lines
+				self.insert_lines(n);
+			}
+
+			([], b'M') => { // delete lines
+				self.delete_lines(n);
+			}
+
+			([] | [b'?'], b'l' | b'h') => { // enable or disable features
+				// while linux used sequences contain ?, windows use non standart format without it (meaning should be the same) - ie. '\x1b[?{number}h/l' or '\x1b[{number}h/l'
+				for feature_id in params {
+					self.set_mode(feature_id[0], byte == b'h');
+				}
+			}
+
+			([] | [b'?'], b'J') => { // clear sequences
+				if param(0) == 2 { // entire screen
+					for row in 0..self.size_rows { self.erase_row(row); }
+				}
+				else if param(0) == 3 { // scrollback only
+					self.clear_scrollback();
+				}
+				else if param(0) == 1 { // from beginning to cursor
+					for row in 0..self.cursor_row { self.erase_row(row); }
+					self.erase_cells(self.cursor_row, 0, self.cursor_column+1);
+				}
+				else /*param(0) == 0*/ { // from cursor to end
+					self.erase_cells(self.cursor_row, self.cursor_column, self.size_columns);
+					for row in self.cursor_row+1..self.size_rows { self.erase_row(row); }
+				}
+			}
+
+			([] | [b'?'], b'K') => { // clear line
+				if param(0) == 2 { // entire line
+					self.erase_cells(self.cursor_row, 0, self.size_columns);
+				}
+				else if param(0) == 1 { // from beginning to cursor
+					self.erase_cells(self.cursor_row, 0, self.cursor_column+1);
+				}
+				else /*param(0) == 0*/ { // from cursor to end of line
+					self.erase_cells(self.cursor_row, self.cursor_column, self.size_columns);
+				}
+			}
+
+			([], b'X') => { // erase characters without moving cursor
+				self.erase_cells(self.cursor_row, self.cursor_column, self.cursor_column+n);
+			}
+
+			([], b'P') => { // delete characters (rest of the line shifts left)
+				let blank = self.blank();
+				let column = self.cursor_column;
+				let line = &mut self.screen[self.cursor_row];
+				let n = min(n, line.cells.len() - column);
+				line.cells.drain(column..column+n);
+				line.cells.extend(std::iter::repeat(blank).take(n));
+				line.updated = true;
+			}
+
+			([], b'@') => { // insert blank characters (rest of the line shifts right)
+				let blank = self.blank();
+				let column = self.cursor_column;
+				let columns = self.size_columns;
+				let line = &mut self.screen[self.cursor_row];
+				let n = min(n, columns - column);
+				line.cells.splice(column..column, std::iter::repeat(blank).take(n));
+				line.cells.truncate(columns);
+				line.updated = true;
+			}
+
+			([], b'H' | b'f') => { // absolute cursor position
+				let r = param(0).max(1);
+				let c = param(1).max(1);
+				if self.origin_mode { self.set_cursor(min(self.scroll_top+r-1, self.scroll_bottom), c-1); }
+				else { self.set_cursor(r-1, c-1); }
+			}
+
+			([], b'G' | b'`') => { // absolute column
+				self.set_cursor(self.cursor_row, n-1);
+			}
+
+			([], b'd') => { // absolute row
+				if self.origin_mode { self.set_cursor(min(self.scroll_top+n-1, self.scroll_bottom), self.cursor_column); }
+				else { self.set_cursor(n-1, self.cursor_column); }
+			}
+
+			([], b'A') => { // cursor up (stops at top margin when inside the region)
+				let top = if self.cursor_row >= self.scroll_top { self.scroll_top } else { 0 };
+				self.set_cursor(self.cursor_row.saturating_sub(n).max(top), self.cursor_column);
+			}
+
+			([], b'B' | b'e') => { // cursor down (stops at bottom margin when inside the region)
+				let bottom = if self.cursor_row <= self.scroll_bottom { self.scroll_bottom } else { self.size_rows-1 };
+				self.set_cursor(min(self.cursor_row+n, bottom), self.cursor_column);
+			}
+
+			([], b'C' | b'a') => { // cursor right
+				self.set_cursor(self.cursor_row, self.cursor_column+n);
+			}
+
+			([], b'D') => { // cursor left
+				self.set_cursor(self.cursor_row, self.cursor_column.saturating_sub(n));
+			}
+
+			([], b'E') => { // cursor to beginning of next line (margins like cursor down)
+				let bottom = if self.cursor_row <= self.scroll_bottom { self.scroll_bottom } else { self.size_rows-1 };
+				self.set_cursor(min(self.cursor_row+n, bottom), 0);
+			}
+
+			([], b'F') => { // cursor to beginning of previous line (margins like cursor up)
+				let top = if self.cursor_row >= self.scroll_top { self.scroll_top } else { 0 };
+				self.set_cursor(self.cursor_row.saturating_sub(n).max(top), 0);
+			}
+
+			([], b's') => { // save cursor
+				self.saved_cursor = (self.cursor_row, self.cursor_column, self.style);
+			}
+
+			([], b'u') => { // restore cursor
+				let (row, column, style) = self.saved_cursor;
+				self.set_cursor(row, column);
+				self.style = style;
+			}
+
+			([], b'c') if param(0) == 0 => { // primary device attributes (vt220 with ansi color)
+				self.respond("\x1b[?62;22c");
+			}
+
+			([b'>'], b'c') if param(0) == 0 => { // secondary device attributes (vt220, version as number - 0.1.0 is 100)
+				let version: usize = env!("CARGO_PKG_VERSION").split('.').take(3).fold(0, |v, part| v * 100 + part.parse::<usize>().unwrap_or(0));
+				self.respond(&format!("\x1b[>1;{};0c", version));
+			}
+
+			([] | [b'?'], b'n') => { // device status report
+				match param(0) {
+					5 => self.respond("\x1b[0n"), // status (always ok)
+					6 => { // cursor position (relative to the scrolling region in origin mode)
+						let row = if self.origin_mode { self.cursor_row.clamp(self.scroll_top, self.scroll_bottom) - self.scroll_top } else { self.cursor_row }; // restored cursor can be outside of it
+						let private = if intermediates.is_empty() { "" } else { "?" };
+						self.respond(&format!("\x1b[{}{};{}R", private, row+1, self.cursor_column+1));
+					}
+					_ => {}
+				}
+			}
+
+			([b' '], b'q') if param(0) <= 6 => { // cursor shape (DECSCUSR)
+				self.cursor_shape = param(0);
+			}
+
+			([b'>'], b'q') if param(0) == 0 => { // terminal name and version (XTVERSION)
+				self.respond(&format!("\x1bP>|termila {}\x1b\\", env!("CARGO_PKG_VERSION")));
+			}
+
+			([b'?', b'$'], b'p') => { // dec private mode state (DECRQM)
+				let mode = param(0);
+				self.respond(&format!("\x1b[?{};{}$y", mode, self.get_mode(mode)));
+			}
+
+			([b'$'], b'p') => { // ansi mode state (none of them is supported)
+				self.respond(&format!("\x1b[{};0$y", param(0)));
+			}
+
+			// TODO: some more
+			_ => { eprintln!("(info)  BUFF: unsupported sequence CSI {:?} {:?} {}", params, String::from_utf8_lossy(intermediates), byte as char); }
+		}
+
+	}
+
+}
+
+
+
+// base64 for OSC 52 (standard alphabet, padding is optional when decoding, whitespace is ignored)
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn format_duration(duration: Duration) -> String { // short readable duration (ie. '15ms', '2.3s', '4m 05s', '1h 02m')
+	let ms = duration.as_millis();
+	if ms < 1000 { return format!("{}ms", ms); }
+	if ms < 60_000 { return format!("{:.1}s", ms as f64 / 1000.0); }
+	if ms < 3_600_000 { return format!("{}m {:02}s", ms / 60_000, ms / 1000 % 60); }
+	return format!("{}h {:02}m", ms / 3_600_000, ms / 60_000 % 60);
+}
+
+pub fn percent_decode(text: &str) -> String { // '%xx' escapes (of urls) to bytes, invalid ones are kept as they are
+	let bytes = text.as_bytes();
+	let mut decoded = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		let hex = text.get(i+1..i+3).filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit())).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+		match (bytes[i], hex) {
+			(b'%', Some(byte)) => { decoded.push(byte); i += 3; }
+			(byte, _) => { decoded.push(byte); i += 1; }
+		}
+	}
+	return String::from_utf8_lossy(&decoded).to_string();
+}
+
+pub fn base64_encode(data: &[u8]) -> String {
+	let mut text = String::with_capacity((data.len() + 2) / 3 * 4);
+	for chunk in data.chunks(3) {
+		let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, b)| bits | (*b as u32) << (16 - 8 * i));
+		for i in 0..4 {
+			if i <= chunk.len() { text.push(BASE64[(bits >> (18 - 6 * i) & 0x3F) as usize] as char); }
+			else { text.push('='); }
+		}
+	}
+	return text;
+}
+
+pub fn base64_decode(text: &str) -> Option<Vec<u8>> {
+	let mut data = Vec::with_capacity(text.len() / 4 * 3);
+	let mut bits = 0u32;
+	let mut count = 0;
+	for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+		if c == b'=' { break; }
+		let value = BASE64.iter().position(|b| *b == c)? as u32;
+		bits = bits << 6 | value;
+		count += 1;
+		if count == 4 {
+			data.extend_from_slice(&[(bits >> 16) as u8, (bits >> 8) as u8, bits as u8]);
+			bits = 0;
+			count = 0;
+		}
+	}
+	match count { // rest without padding
+		0 => {}
+		2 => data.push((bits >> 4) as u8),
+		3 => data.extend_from_slice(&[(bits >> 10) as u8, (bits >> 2) as u8]),
+		_ => return None,
+	}
+	return Some(data);
+}
+
+#[cfg(test)]
+mod tests;
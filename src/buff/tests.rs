@@ -0,0 +1,356 @@
+// replays recorded terminal output (tests/fixtures/<name>.bin) and compares the resulting buffer with golden snapshots (tests/fixtures/<name>.snap)
+// after intended changes, run 'TERMILA_BLESS=1 cargo test' to write the snapshots again and check the diff
+
+use super::*;
+use crate::parser::PARSER;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+
+fn replay(name: &str, rows: usize, columns: usize) {
+
+	let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures");
+	let bytes = fs::read(dir.join(format!("{}.bin", name))).unwrap_or_else(|e| panic!("{}: cant read fixture ({})", name, e));
+
+	// whole stream at once
+	let mut buff = BUFF::new(rows, columns, 1000);
+	PARSER::new().advance(&mut buff, &bytes);
+	let snapshot = buff.snapshot();
+
+	// the same stream in small chunks (sequences and utf-8 characters are cut between them, like pty reads do)
+	let mut chunked = BUFF::new(rows, columns, 1000);
+	let mut parser = PARSER::new();
+	for chunk in bytes.chunks(7) {
+		parser.advance(&mut chunked, chunk);
+	}
+	assert_eq!(chunked.snapshot(), snapshot, "{}: result depends on how the output is split", name);
+
+	let path = dir.join(format!("{}.snap", name));
+	if env::var("TERMILA_BLESS").is_ok_and(|v| v == "1") {
+		fs::write(&path, &snapshot).unwrap();
+		return;
+	}
+
+	let expected = fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: cant read snapshot ({}), run with TERMILA_BLESS=1 to create it", name, e));
+	if snapshot != expected {
+		let line = snapshot.lines().zip(expected.lines()).position(|(a, b)| a != b).unwrap_or(min(snapshot.lines().count(), expected.lines().count()));
+		panic!("{}: buffer differs from snapshot at line {}\n--- expected\n{}\n--- got\n{}", name, line+1, expected, snapshot);
+	}
+}
+
+
+// hand written fragments of what vttest checks
+
+#[test]
+fn vttest_cursor() { replay("vttest_cursor", 24, 80); }
+
+#[test]
+fn vttest_scroll() { replay("vttest_scroll", 24, 80); }
+
+#[test]
+fn vttest_edit() { replay("vttest_edit", 24, 80); }
+
+#[test]
+fn vttest_sgr() { replay("vttest_sgr", 24, 80); }
+
+#[test]
+fn vttest_alternate() { replay("vttest_alternate", 24, 80); }
+
+
+// hand written like vttest ones, with what the parser bugs in README were reported with (see tests/fixtures/README.md)
+
+#[test]
+fn nano() { replay("nano", 24, 80); }
+
+#[test]
+fn htop() { replay("htop", 24, 80); }
+
+
+// recorded from real programs (see tests/fixtures/README.md)
+
+#[test]
+fn vim() { replay("vim", 24, 80); }
+
+#[test]
+fn git_log() { replay("git", 24, 80); }
+
+#[test]
+fn less() { replay("less", 24, 80); }
+
+#[test]
+fn top() { replay("top", 24, 80); }
+
+#[test]
+fn tmux() { replay("tmux", 24, 80); }
+
+#[test]
+fn bash() { replay("bash", 24, 80); }
+
+
+// behaviour that doesnt need a whole fixture
+
+fn feed(buff: &mut BUFF, text: &str) {
+	PARSER::new().advance(buff, text.as_bytes());
+}
+
+#[test]
+fn wrap_is_delayed_at_last_column() {
+	let mut buff = BUFF::new(3, 5, 10);
+	feed(&mut buff, "abcde");
+	assert_eq!((buff.cursor_row, buff.cursor_column, buff.cursor_wrap), (0, 4, true));
+	feed(&mut buff, "\rX");
+	assert_eq!(buff.screen_text(), vec!["Xbcde", "", ""]);
+	feed(&mut buff, "bcdef");
+	assert_eq!(buff.screen_text(), vec!["Xbcde", "f", ""]);
+}
+
+#[test]
+fn lines_leaving_the_screen_go_to_scrollback() {
+	let mut buff = BUFF::new(2, 10, 2);
+	feed(&mut buff, "1\r\n2\r\n3\r\n4\r\n5");
+	assert_eq!(buff.scrollback.iter().map(BUFF::line_text).collect::<Vec<_>>(), vec!["2", "3"]);
+	assert_eq!(buff.screen_text(), vec!["4", "5"]);
+
+	// but not from the alternate screen
+	feed(&mut buff, "\x1b[?1049h\r\na\r\nb\r\nc\x1b[?1049l");
+	assert_eq!(buff.scrollback.len(), 2);
+	assert_eq!(buff.screen_text(), vec!["4", "5"]);
+}
+
+#[test]
+fn resize_keeps_cursor_line() {
+	let mut buff = BUFF::new(4, 10, 10);
+	feed(&mut buff, "1\r\n2\r\n3");
+	buff.resize(2, 5);
+	assert_eq!(buff.screen_text(), vec!["2", "3"]);
+	assert_eq!(buff.scrollback.iter().map(BUFF::line_text).collect::<Vec<_>>(), vec!["1"]);
+	assert_eq!((buff.cursor_row, buff.cursor_column), (1, 1));
+}
+
+#[test]
+fn resize_while_alternate_keeps_primary_cursor_line() {
+	let mut buff = BUFF::new(5, 10, 10);
+	feed(&mut buff, "1\r\n2\r\n3\r\n$ vim\x1b[?1049hfull screen");
+	buff.resize(2, 10);
+	feed(&mut buff, "\x1b[?1049l");
+	assert_eq!(buff.screen_text(), vec!["3", "$ vim"]);
+	assert_eq!(buff.scrollback.iter().map(BUFF::line_text).collect::<Vec<_>>(), vec!["1", "2"]);
+	assert_eq!((buff.cursor_row, buff.cursor_column), (1, 5));
+}
+
+#[test]
+fn alternate_screen_twice_keeps_primary_cursor() {
+	let mut buff = BUFF::new(5, 10, 10);
+	feed(&mut buff, "$ vim\x1b[?1049h\x1b[3;4H\x1b[?1049h\x1b[?1049l");
+	assert_eq!(buff.screen_text(), vec!["$ vim", "", "", "", ""]);
+	assert_eq!((buff.cursor_row, buff.cursor_column), (0, 5));
+}
+
+#[test]
+fn next_and_previous_line_stop_at_margins() {
+	let mut buff = BUFF::new(6, 10, 10);
+	feed(&mut buff, "\x1b[2;4r\x1b[3;5H\x1b[9E");
+	assert_eq!((buff.cursor_row, buff.cursor_column), (3, 0));
+	feed(&mut buff, "\x1b[9F");
+	assert_eq!((buff.cursor_row, buff.cursor_column), (1, 0));
+	feed(&mut buff, "\x1b[1;1H\x1b[9E"); // above the region too (like cursor down)
+	assert_eq!((buff.cursor_row, buff.cursor_column), (3, 0));
+	feed(&mut buff, "\x1b[6;1H\x1b[9E"); // below it only the screen stops it
+	assert_eq!((buff.cursor_row, buff.cursor_column), (5, 0));
+}
+
+#[test]
+fn reports() {
+	let mut buff = BUFF::new(10, 20, 10);
+	let reply = |buff: &mut BUFF, query: &str| -> String {
+		feed(buff, query);
+		String::from_utf8(std::mem::take(&mut buff.responses)).unwrap()
+	};
+
+	assert_eq!(reply(&mut buff, "\x1b[c"), "\x1b[?62;22c");
+	assert!(reply(&mut buff, "\x1b[>c").starts_with("\x1b[>1;"));
+	assert_eq!(reply(&mut buff, "\x1b[5n"), "\x1b[0n");
+	assert_eq!(reply(&mut buff, "\x1b[3;7H\x1b[6n"), "\x1b[3;7R");
+	assert_eq!(reply(&mut buff, "\x1b[2;5r\x1b[?6h\x1b[2;3H\x1b[6n\x1b[?6n"), "\x1b[2;3R\x1b[?2;3R"); // origin mode
+	assert_eq!(reply(&mut buff, "\x1b[?6l\x1b[r\x1b[2;1H\x1b7\x1b[5;8r\x1b[?6h\x1b8\x1b[6n"), "\x1b[1;1R"); // restored above the region
+	assert_eq!(reply(&mut buff, "\x1b[>q"), format!("\x1bP>|termila {}\x1b\\", env!("CARGO_PKG_VERSION")));
+	assert_eq!(reply(&mut buff, "\x1b[?6$p\x1b[?1049$p\x1b[?7$p\x1b[?12345$p\x1b[4$p"), "\x1b[?6;1$y\x1b[?1049;2$y\x1b[?7;3$y\x1b[?12345;0$y\x1b[4;0$y");
+	assert_eq!(reply(&mut buff, "\x1b[1c\x1b[7n"), ""); // nothing for unknown queries
+}
+
+#[test]
+fn titles() {
+	let mut buff = BUFF::new(10, 20, 10);
+	feed(&mut buff, "\x1b]0;both\x07");
+	assert_eq!((buff.icon_name.as_str(), buff.title.as_str()), ("both", "both"));
+	feed(&mut buff, "\x1b]2;title\x1b\\\x1b]1;icon\x07");
+	assert_eq!((buff.icon_name.as_str(), buff.title.as_str()), ("icon", "title"));
+
+	// push both, change them, pop only the title and then the icon name from the older entry
+	feed(&mut buff, "\x1b[22;0t\x1b[22;2t\x1b]0;vim\x07");
+	feed(&mut buff, "\x1b[23;2t");
+	assert_eq!((buff.icon_name.as_str(), buff.title.as_str()), ("vim", "title"));
+	feed(&mut buff, "\x1b[23;1t");
+	assert_eq!((buff.icon_name.as_str(), buff.title.as_str()), ("icon", "title"));
+	feed(&mut buff, "\x1b[23;0t"); // empty stack
+	assert_eq!((buff.icon_name.as_str(), buff.title.as_str()), ("icon", "title"));
+
+	// stack has limited size
+	for _ in 0..BUFF::MAX_TITLE_STACK+5 { feed(&mut buff, "\x1b[22t"); }
+	assert_eq!(buff.title_stack.len(), BUFF::MAX_TITLE_STACK);
+}
+
+#[test]
+fn hyperlinks() {
+	let mut buff = BUFF::new(3, 30, 10);
+	feed(&mut buff, "\x1b]8;;https://a.example\x1b\\a\x1b[1mb\x1b[0mc\x1b]8;;\x1b\\ d \x1b]8;id=1;https://b.example\x07e\x1b]8;;\x07 \x1b]8;;https://a.example\x07f");
+
+	// sgr reset keeps the link, the same uri gets the same id
+	let links: Vec<u32> = buff.screen[0].cells[..9].iter().map(|cell| cell.style.link).collect();
+	assert_eq!(links, vec![1, 1, 1, 0, 0, 0, 2, 0, 1]);
+	assert_eq!(buff.links, vec!["https://a.example", "https://b.example"]);
+
+	// rendered as anchors referring to the link by id
+	let html = BUFF::render_line(&buff.screen[0], &buff.links, None);
+	assert!(html.starts_with("<a data-link=\"1\" title=\"https://a.example\">a</a><a data-link=\"1\" title=\"https://a.example\"><span style=\"font-weight: bold;\">b</span></a>"), "{}", html);
+	assert!(html.contains("<a data-link=\"2\" title=\"https://b.example\">e</a>"), "{}", html);
+}
+
+#[test]
+fn hyperlinks_are_forgotten() {
+	let mut buff = BUFF::new(3, 30, 5);
+	for i in 0..2000 {
+		feed(&mut buff, &format!("\x1b]8;;https://example/{}\x1b\\{}\x1b]8;;\x1b\\\r\n", i, i));
+	}
+
+	// only uris of the lines still in scrollback or on screen are kept
+	assert!(buff.links.len() <= BUFF::MIN_LINKS_COLLECT, "{}", buff.links.len());
+	assert_eq!(buff.link_ids.len(), buff.links.iter().filter(|link| !link.is_empty()).count());
+	let link = buff.screen[1].cells[0].style.link;
+	assert_eq!(buff.links[link as usize - 1], "https://example/1999");
+	assert_eq!(buff.link_ids["https://example/1999"], link);
+}
+
+#[test]
+fn base64() {
+	for (data, text) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("hello world\n", "aGVsbG8gd29ybGQK")] {
+		assert_eq!(base64_encode(data.as_bytes()), text);
+		assert_eq!(base64_decode(text).unwrap(), data.as_bytes());
+	}
+	assert_eq!(base64_decode("Zm9vYg").unwrap(), b"foob"); // without padding
+	assert_eq!(base64_decode("Zm9v\nYmFy").unwrap(), b"foobar");
+	assert!(base64_decode("Zm9v!").is_none());
+	assert!(base64_decode("Z").is_none());
+	assert_eq!(base64_decode(&base64_encode("žluť😀".as_bytes())).unwrap(), "žluť😀".as_bytes());
+}
+
+#[test]
+fn clipboard() {
+	let mut buff = BUFF::new(3, 10, 10);
+	feed(&mut buff, "\x1b]52;c;aGVsbG8=\x07\x1b]52;;?\x07\x1b]52;p;?\x1b\\\x1b]52;c;!!\x07");
+	let requests: Vec<String> = buff.clipboard.iter().map(|request| match request {
+		BUFF_clipboard::Set(text) => format!("set {}", text),
+		BUFF_clipboard::Query(selection) => format!("query {}", selection),
+	}).collect();
+	assert_eq!(requests, vec!["set hello", "query c", "query p"]);
+}
+
+#[test]
+fn shell_marks() {
+	let mut buff = BUFF::new(3, 20, 10);
+	feed(&mut buff, "\x1b]7;file://host/a%20b/%C3%A9\x07\x1b]133;A\x07$ \x1b]133;B\x07ls\r\n\x1b]133;C\x07x\r\n");
+	assert_eq!((buff.cwd_host.as_str(), buff.cwd.as_str()), ("host", "/a b/\u{e9}"));
+	assert_eq!(buff.screen[0].mark.describe(), "prompt command=3 cwd=/a b/\u{e9}");
+	assert_eq!(buff.screen[1].mark.describe(), "output");
+
+	// exit status goes to the prompt of the command even when it is in scrollback already
+	feed(&mut buff, "y\r\nz\r\n\x1b]133;D;1\x07\x1b]133;A\x07$ ");
+	assert_eq!(buff.scrollback[0].mark.describe(), "prompt command=3 exit=1 cwd=/a b/\u{e9}");
+
+	// D without a command (empty line) is ignored, clearing the screen removes marks
+	feed(&mut buff, "\r\n\x1b]133;D;0\x07");
+	assert_eq!(buff.screen[1].mark.describe(), "prompt cwd=/a b/\u{e9}");
+	feed(&mut buff, "\x1b[2J");
+	assert!(buff.screen.iter().all(|line| line.mark == BUFF_mark::default()));
+}
+
+#[test]
+fn command_blocks() {
+	let mut buff = BUFF::new(4, 20, 10);
+	feed(&mut buff, "\x1b]133;A\x07$ \x1b]133;B\x07make\r\n\x1b]133;C\x07a\r\n\r\nb\r\n\x1b]133;D;0\x07\x1b]133;A\x07$ ");
+	let blocks: Vec<usize> = buff.scrollback.iter().chain(buff.screen.iter()).map(|line| line.block).collect();
+	assert_eq!(blocks, vec![1, 1, 1, 1, 2]); // empty output line too
+	assert!(buff.scrollback[0].mark.duration.is_some());
+
+	assert_eq!(format_duration(Duration::from_millis(15)), "15ms");
+	assert_eq!(format_duration(Duration::from_millis(2345)), "2.3s");
+	assert_eq!(format_duration(Duration::from_secs(245)), "4m 05s");
+	assert_eq!(format_duration(Duration::from_secs(3720)), "1h 02m");
+}
+
+#[test]
+fn keyboard_modes() {
+	let mut buff = BUFF::new(3, 10, 10);
+	feed(&mut buff, "\x1b[?1h\x1b=\x1b[?2004h\x1b[>4;2m\x1b[?4m");
+	assert!(buff.key_modes.application_cursor && buff.key_modes.application_keypad && buff.key_modes.bracketed_paste);
+	assert_eq!(buff.get_mode(2004), 1);
+	assert_eq!(buff.key_modes.modify_other_keys, 2);
+
+	// one mouse tracking mode and encoding at a time
+	feed(&mut buff, "\x1b[?1000h\x1b[?1003h\x1b[?1000l\x1b[?1006h");
+	assert_eq!((buff.key_modes.mouse_tracking, buff.key_modes.mouse_encoding), (1003, 1006));
+	assert_eq!((buff.get_mode(1000), buff.get_mode(1003)), (2, 1));
+	feed(&mut buff, "\x1b[?1003l\x1b[?1006l");
+	assert_eq!((buff.key_modes.mouse_tracking, buff.key_modes.mouse_encoding), (0, 0));
+	feed(&mut buff, "\x1b[?1004h");
+	assert!(buff.key_modes.focus_reporting);
+	assert_eq!(buff.get_mode(1004), 1);
+
+	// kitty flags stack, query, and separate stack of the alternate screen
+	feed(&mut buff, "\x1b[>1u\x1b[>3u\x1b[=4;2u\x1b[?u\x1b[?1049h\x1b[?u\x1b[>8u\x1b[?1049l\x1b[?u\x1b[<u\x1b[?u\x1b[<5u\x1b[?u");
+	assert_eq!(String::from_utf8_lossy(&buff.responses), "\x1b[>4;2m\x1b[?7u\x1b[?0u\x1b[?7u\x1b[?1u\x1b[?0u");
+}
+
+#[test]
+fn synchronized_output() {
+	let mut buff = BUFF::new(3, 10, 10);
+	feed(&mut buff, "\x1b[?2026h\x1b[?2026$p");
+	assert!(buff.synchronizing());
+
+	// frame is shown anyway after timeout
+	buff.synchronized = Some(Instant::now() - BUFF::SYNC_TIMEOUT);
+	assert!(!buff.synchronizing());
+
+	feed(&mut buff, "\x1b[?2026l\x1b[?2026$p");
+	assert!(!buff.synchronizing());
+	assert_eq!(String::from_utf8_lossy(&buff.responses), "\x1b[?2026;1$y\x1b[?2026;2$y");
+}
+
+#[test]
+fn cursor() {
+	let mut buff = BUFF::new(3, 10, 10);
+	feed(&mut buff, "ab\x1b[1mcd\x1b[0m\x1b[3D");
+
+	// cursor cell gets its own element (past the end of the text too)
+	assert_eq!(BUFF::render_line(&buff.screen[0], &buff.links, Some(1)), "a<span class=\"cursor\">b</span><span style=\"font-weight: bold;\">cd</span>");
+	assert_eq!(BUFF::render_line(&buff.screen[0], &buff.links, Some(2)), "ab<span style=\"font-weight: bold;\"><span class=\"cursor\">c</span></span><span style=\"font-weight: bold;\">d</span>");
+	assert_eq!(BUFF::render_line(&buff.screen[0], &buff.links, Some(5)), "ab<span style=\"font-weight: bold;\">cd</span>&nbsp;<span class=\"cursor\">&nbsp;</span>");
+
+	// moved cursor renders old and new line again
+	buff.update_full();
+	assert_eq!(buff.cursor_displayed, Some((buff.screen[0].id, 1)));
+	feed(&mut buff, "\x1b[2;3H");
+	let js = buff.update_partial().unwrap();
+	assert!(js.contains("outerHTML=`<span id=\"console-1\">ab<span style=\"font-weight: bold;\">cd</span></span>`"), "{}", js);
+	assert!(js.contains("outerHTML=`<span id=\"console-2\">&nbsp;&nbsp;<span class=\"cursor\">&nbsp;</span></span>`"), "{}", js);
+	assert!(buff.update_partial().is_none());
+
+	// hidden (DECTCEM) and shaped (DECSCUSR)
+	feed(&mut buff, "\x1b[?25l\x1b[6 q\x1b[?25$p");
+	let js = buff.update_partial().unwrap();
+	assert!(js.contains("c.dataset.cursor=6;") && !js.contains("class=\"cursor\""), "{}", js);
+	assert_eq!(String::from_utf8_lossy(&buff.responses), "\x1b[?25;2$y");
+	feed(&mut buff, "\x1b[?25h\x1b[0 q");
+	assert_eq!(buff.displayed_shape(), buff.cursor_default);
+}
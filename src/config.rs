@@ -0,0 +1,235 @@
+// minimal TOML reader for config and theme files
+// (supports tables, arrays of tables, strings, integers, floats, booleans, arrays and inline tables - enough for termila config and alacritty themes)
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CONFIG_value {
+	String(String),
+	Integer(i64),
+	Float(f64),
+	Boolean(bool),
+	Array(Vec<CONFIG_value>),
+	Table(Vec<(String, CONFIG_value)>), // inline table '{ key = value }'
+}
+impl CONFIG_value {
+
+	pub fn as_str(&self) -> Option<&str> {
+		match self { CONFIG_value::String(s) => Some(s), _ => None }
+	}
+
+	pub fn as_integer(&self) -> Option<i64> {
+		match self { CONFIG_value::Integer(i) => Some(*i), _ => None }
+	}
+
+	pub fn get(&self, key: &str) -> Option<&CONFIG_value> {
+		match self { CONFIG_value::Table(t) => t.iter().find(|(k, _)| k == key).map(|(_, v)| v), _ => None }
+	}
+
+}
+
+
+// parse whole file into list of ('table.subtable.key', value) in file order
+// arrays of tables are numbered, so second '[[a]]' with key 'b' becomes 'a.1.b'
+pub fn parse_toml(text: &str) -> Result<Vec<(String, CONFIG_value)>, String> {
+
+	let mut result = vec![];
+	let mut table = String::new();
+	let mut array_tables: Vec<(String, usize)> = vec![]; // counters for '[[name]]'
+
+	let chars: Vec<char> = text.chars().collect();
+	let mut p = READER { chars: &chars, pos: 0, line: 1 };
+
+	loop {
+		p.skip_blank(true);
+		let Some(c) = p.peek() else { break };
+
+		if c == '[' { // table header
+			p.pos += 1;
+			let array = p.peek() == Some('[');
+			if array { p.pos += 1; }
+			p.skip_blank(false);
+			let name = p.key()?;
+			p.skip_blank(false);
+			if !p.eat(']') || (array && !p.eat(']')) {
+				return Err(format!("line {}: expected ']' after table name", p.line));
+			}
+			if array {
+				let index = match array_tables.iter_mut().find(|(n, _)| *n == name) {
+					Some((_, count)) => { *count += 1; *count }
+					None => { array_tables.push((name.clone(), 0)); 0 }
+				};
+				table = format!("{}.{}", name, index);
+			}
+			else {
+				table = name;
+			}
+		}
+
+		else { // key = value
+			let key = p.key()?;
+			p.skip_blank(false);
+			if !p.eat('=') {
+				return Err(format!("line {}: expected '=' after key '{}'", p.line, key));
+			}
+			p.skip_blank(false);
+			let value = p.value()?;
+			result.push((if table.is_empty() { key } else { format!("{}.{}", table, key) }, value));
+		}
+
+		// nothing else is allowed on the line
+		p.skip_blank(false);
+		match p.peek() {
+			None | Some('\n') => {}
+			Some(c) => return Err(format!("line {}: unexpected character '{}'", p.line, c)),
+		}
+	}
+
+	return Ok(result);
+}
+
+
+struct READER<'t> {
+	chars: &'t [char],
+	pos: usize,
+	line: usize,
+}
+impl READER<'_> {
+
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.pos).copied()
+	}
+
+	fn eat(&mut self, c: char) -> bool {
+		if self.peek() == Some(c) { self.pos += 1; true } else { false }
+	}
+
+	fn skip_blank(&mut self, newlines: bool) { // skips whitespace and comments (and newlines if requested)
+		while let Some(c) = self.peek() {
+			if c == '#' {
+				while self.peek().is_some_and(|c| c != '\n') { self.pos += 1; }
+			}
+			else if c == '\n' && newlines { self.line += 1; self.pos += 1; }
+			else if c == ' ' || c == '\t' || c == '\r' { self.pos += 1; }
+			else { break; }
+		}
+	}
+
+	fn key(&mut self) -> Result<String, String> { // dotted key, parts can be bare or quoted
+		let mut parts = vec![];
+		loop {
+			self.skip_blank(false);
+			let part = match self.peek() {
+				Some('"') | Some('\'') => self.string()?,
+				_ => {
+					let start = self.pos;
+					while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') { self.pos += 1; }
+					if start == self.pos {
+						return Err(format!("line {}: expected key", self.line));
+					}
+					self.chars[start..self.pos].iter().collect()
+				}
+			};
+			parts.push(part);
+			self.skip_blank(false);
+			if !self.eat('.') { break; }
+		}
+		return Ok(parts.join("."));
+	}
+
+	fn string(&mut self) -> Result<String, String> {
+		let quote = self.peek().unwrap();
+		self.pos += 1;
+		let mut s = String::new();
+		loop {
+			match self.peek() {
+				None | Some('\n') => return Err(format!("line {}: unterminated string", self.line)),
+				Some(c) if c == quote => { self.pos += 1; break; }
+				Some('\\') if quote == '"' => {
+					self.pos += 1;
+					let escaped = match self.peek() {
+						Some('n') => '\n',
+						Some('t') => '\t',
+						Some('r') => '\r',
+						Some('e') => '\x1b',
+						Some('"') => '"',
+						Some('\\') => '\\',
+						Some('u') => {
+							let hex: String = self.chars.get(self.pos+1..self.pos+5).unwrap_or(&[]).iter().collect();
+							self.pos += 4;
+							u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32).ok_or(format!("line {}: invalid unicode escape", self.line))?
+						}
+						_ => return Err(format!("line {}: invalid escape in string", self.line)),
+					};
+					s.push(escaped);
+					self.pos += 1;
+				}
+				Some(c) => { s.push(c); self.pos += 1; }
+			}
+		}
+		return Ok(s);
+	}
+
+	fn value(&mut self) -> Result<CONFIG_value, String> {
+		match self.peek() {
+
+			Some('"') | Some('\'') => Ok(CONFIG_value::String(self.string()?)),
+
+			Some('[') => { // array (may span multiple lines)
+				self.pos += 1;
+				let mut items = vec![];
+				loop {
+					self.skip_blank(true);
+					if self.eat(']') { break; }
+					items.push(self.value()?);
+					self.skip_blank(true);
+					if self.eat(']') { break; }
+					if !self.eat(',') {
+						return Err(format!("line {}: expected ',' or ']' in array", self.line));
+					}
+				}
+				Ok(CONFIG_value::Array(items))
+			}
+
+			Some('{') => { // inline table
+				self.pos += 1;
+				let mut items = vec![];
+				loop {
+					self.skip_blank(false);
+					if self.eat('}') { break; }
+					let key = self.key()?;
+					self.skip_blank(false);
+					if !self.eat('=') {
+						return Err(format!("line {}: expected '=' after key '{}'", self.line, key));
+					}
+					self.skip_blank(false);
+					items.push((key, self.value()?));
+					self.skip_blank(false);
+					if self.eat('}') { break; }
+					if !self.eat(',') {
+						return Err(format!("line {}: expected ',' or '}}' in inline table", self.line));
+					}
+				}
+				Ok(CONFIG_value::Table(items))
+			}
+
+			_ => { // bare value
+				let start = self.pos;
+				while self.peek().is_some_and(|c| !" \t\r\n,]}#".contains(c)) { self.pos += 1; }
+				let word: String = self.chars[start..self.pos].iter().collect();
+				let number = word.replace('_', "");
+				if word == "true" { Ok(CONFIG_value::Boolean(true)) }
+				else if word == "false" { Ok(CONFIG_value::Boolean(false)) }
+				else if let Some(hex) = number.strip_prefix("0x") { i64::from_str_radix(hex, 16).map(CONFIG_value::Integer).map_err(|_| format!("line {}: invalid number '{}'", self.line, word)) }
+				else if let Ok(i) = number.parse::<i64>() { Ok(CONFIG_value::Integer(i)) }
+				else if let Ok(f) = number.parse::<f64>() { Ok(CONFIG_value::Float(f)) }
+				else { Err(format!("line {}: invalid value '{}'", self.line, word)) }
+			}
+
+		}
+	}
+
+}
+
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,64 @@
+// toml subset reader unit tests (keys are flattened to 'table.key' in file order)
+
+use super::*;
+
+fn string(s: &str) -> CONFIG_value { CONFIG_value::String(s.to_string()) }
+
+#[test]
+fn values() {
+	let parsed = parse_toml("
+		# comment
+		shell = \"bash\" # comment after value
+		quoted = 'C:\\path'
+		escapes = \"a\\tb\\u00e9\\e\"
+		fps = 30
+		big = 1_000
+		hex = 0xff
+		ratio = 0.5
+		hold = true
+		args = [\"-l\",
+			\"-i\", # inside array
+		]
+		point = { x = 1, y = \"two\" }
+	").unwrap();
+	assert_eq!(parsed, vec![
+		("shell".to_string(), string("bash")),
+		("quoted".to_string(), string("C:\\path")),
+		("escapes".to_string(), string("a\tb\u{e9}\x1b")),
+		("fps".to_string(), CONFIG_value::Integer(30)),
+		("big".to_string(), CONFIG_value::Integer(1000)),
+		("hex".to_string(), CONFIG_value::Integer(255)),
+		("ratio".to_string(), CONFIG_value::Float(0.5)),
+		("hold".to_string(), CONFIG_value::Boolean(true)),
+		("args".to_string(), CONFIG_value::Array(vec![string("-l"), string("-i")])),
+		("point".to_string(), CONFIG_value::Table(vec![("x".to_string(), CONFIG_value::Integer(1)), ("y".to_string(), string("two"))])),
+	]);
+	assert_eq!(parsed[9].1.get("y").and_then(|y| y.as_str()), Some("two"));
+}
+
+#[test]
+fn tables() {
+	let parsed = parse_toml("
+		top = 1
+		[colors.primary]
+		foreground = '#ffffff'
+		[\"quoted table\"]
+		a.b = 2
+		[[hints]]
+		x = 1
+		[[hints]]
+		x = 2
+	").unwrap();
+	let keys: Vec<&str> = parsed.iter().map(|(k, _)| k.as_str()).collect();
+	assert_eq!(keys, vec!["top", "colors.primary.foreground", "quoted table.a.b", "hints.0.x", "hints.1.x"]);
+}
+
+#[test]
+fn errors() {
+	assert_eq!(parse_toml("a = 1\nb 2"), Err("line 2: expected '=' after key 'b'".to_string()));
+	assert_eq!(parse_toml("a = \"open"), Err("line 1: unterminated string".to_string()));
+	assert_eq!(parse_toml("[table\n"), Err("line 1: expected ']' after table name".to_string()));
+	assert_eq!(parse_toml("a = 1 2"), Err("line 1: unexpected character '2'".to_string()));
+	assert_eq!(parse_toml("\n\na = nope"), Err("line 3: invalid value 'nope'".to_string()));
+	assert_eq!(parse_toml("a = [1 2]"), Err("line 1: expected ',' or ']' in array".to_string()));
+}
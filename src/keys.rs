@@ -0,0 +1,416 @@
+// keyboard encoding (key events from the webview to bytes for the application, xterm compatible)
+// (the page sends KeyboardEvent code, key and modifiers, pasted text is cleaned and bracketed here too, mouse events are reported here as well)
+// applications can ask for unambiguous encodings: kitty keyboard protocol (https://sw.kovidgoyal.net/kitty/keyboard-protocol/) or xterm modifyOtherKeys
+
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct KEYS_modes { // how keys are encoded, set by the application (see BUFF)
+	pub application_cursor: bool, // DECCKM, cursor keys send 'ESC O x' instead of 'CSI x'
+	pub application_keypad: bool, // DECKPAM, keypad sends 'ESC O x' instead of its characters
+	pub kitty_flags: u8, // kitty keyboard protocol enhancements (0 means legacy encoding)
+	pub modify_other_keys: u8, // xterm modifyOtherKeys level (0 off, 1 only keys without legacy encoding, 2 all modified keys)
+	pub bracketed_paste: bool, // DECSET 2004, pasted text is wrapped in 'CSI 200~' and 'CSI 201~'
+	pub mouse_tracking: usize, // reported mouse events by DECSET mode (0 none, 9 presses, 1000 presses and releases, 1002 and moves with button, 1003 all moves)
+	pub mouse_encoding: usize, // mouse report format by DECSET mode (0 'CSI M' with bytes, 1005 utf-8, 1006 SGR, 1015 urxvt)
+	pub focus_reporting: bool, // DECSET 1004, focus in and out are sent as 'CSI I' and 'CSI O'
+}
+impl KEYS_modes {
+	#[allow(dead_code)] // used by tests (any flag disambiguates)
+	pub const KITTY_DISAMBIGUATE: u8 = 1; // esc and modified text keys as 'CSI <code> u'
+	pub const KITTY_EVENT_TYPES: u8 = 2; // report repeat and release
+	pub const KITTY_ALTERNATE_KEYS: u8 = 4; // report shifted key too
+	pub const KITTY_ALL_KEYS: u8 = 8; // report all keys (text and modifiers too) as escape codes
+	pub const KITTY_TEXT: u8 = 16; // report text of the key (with KITTY_ALL_KEYS)
+	pub const KITTY_FLAGS: u8 = 31; // all known flags
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum KEYS_kind { // kitty event type numbers
+	Press = 1,
+	Repeat = 2,
+	Release = 3,
+}
+
+pub struct KEYS_event<'a> { // KeyboardEvent (AltGr is not reported as ctrl+alt, the page clears them for it)
+	pub code: &'a str, // physical key (ie. 'KeyA', 'Numpad5', 'F1')
+	pub key: &'a str, // character or key name given by the keyboard layout (ie. 'a', 'A', 'Enter')
+	pub shift: bool,
+	pub alt: bool,
+	pub ctrl: bool,
+	pub kind: KEYS_kind, // legacy encodings send nothing on release
+}
+#[derive(Clone, Copy, PartialEq)]
+pub enum KEYS_mouse_action {
+	Press,
+	Release,
+	Move,
+}
+
+pub struct KEYS_mouse { // MouseEvent converted to cell of the screen
+	pub action: KEYS_mouse_action,
+	pub button: u8, // xterm button number (0 left, 1 middle, 2 right, 3 none while moving, 64-67 wheel up, down, left, right)
+	pub row: usize, // from 0
+	pub column: usize, // from 0
+	pub shift: bool,
+	pub alt: bool,
+	pub ctrl: bool,
+}
+
+impl KEYS_event<'_> {
+
+	// modifier parameter of xterm sequences ('CSI 1;<modifier> x'), 1 means none
+	pub fn modifier(&self) -> u8 {
+		1 + self.shift as u8 + 2 * self.alt as u8 + 4 * self.ctrl as u8
+	}
+
+}
+
+
+// bytes for the key, None for keys that dont send anything (ie. F13, media keys)
+pub fn encode(event: &KEYS_event, modes: &KEYS_modes) -> Option<Vec<u8>> {
+
+	if modes.kitty_flags != 0 { return encode_kitty(event, modes); }
+	if event.kind == KEYS_kind::Release { return None; }
+
+	let modifier = event.modifier();
+
+	// keypad in application mode (only while it gives characters, with num lock off it gives cursor keys)
+	if modes.application_keypad && event.code.starts_with("Numpad") && (event.key.chars().count() == 1 || event.key == "Enter") {
+		let byte = match event.code {
+			"NumpadEnter" => Some(b'M'),
+			"NumpadAdd" => Some(b'k'),
+			"NumpadSubtract" => Some(b'm'),
+			"NumpadMultiply" => Some(b'j'),
+			"NumpadDivide" => Some(b'o'),
+			"NumpadDecimal" => Some(b'n'),
+			"NumpadEqual" => Some(b'X'),
+			code => code.strip_prefix("Numpad").and_then(|digit| digit.parse::<u8>().ok()).filter(|digit| *digit <= 9).map(|digit| b'p' + digit),
+		};
+		if let Some(byte) = byte { return Some(vec![0x1b, b'O', byte]); }
+	}
+
+	// cursor keys, Home, End and F1-F4 ('CSI 1;<modifier> x' when modified)
+	let cursor = match event.key {
+		"ArrowUp" => Some(b'A'),
+		"ArrowDown" => Some(b'B'),
+		"ArrowRight" => Some(b'C'),
+		"ArrowLeft" => Some(b'D'),
+		"Home" => Some(b'H'),
+		"End" => Some(b'F'),
+		"F1" => Some(b'P'),
+		"F2" => Some(b'Q'),
+		"F3" => Some(b'R'),
+		"F4" => Some(b'S'),
+		_ => None,
+	};
+	if let Some(byte) = cursor {
+		let ss3 = modes.application_cursor || event.key.starts_with('F'); // F1-F4 are always 'ESC O x' (as on vt100 PF1-PF4)
+		return Some(
+			if modifier > 1 { format!("\x1b[1;{}{}", modifier, byte as char) }
+			else if ss3 { format!("\x1bO{}", byte as char) }
+			else { format!("\x1b[{}", byte as char) }
+			.into_bytes()
+		);
+	}
+
+	// editing keys and F5-F12 ('CSI <number>;<modifier> ~' when modified)
+	let number = match event.key {
+		"Insert" => 2,
+		"Delete" => 3,
+		"PageUp" => 5,
+		"PageDown" => 6,
+		"F5" => 15,
+		"F6" => 17,
+		"F7" => 18,
+		"F8" => 19,
+		"F9" => 20,
+		"F10" => 21,
+		"F11" => 23,
+		"F12" => 24,
+		_ => 0,
+	};
+	if number != 0 {
+		return Some(if modifier > 1 { format!("\x1b[{};{}~", number, modifier) } else { format!("\x1b[{}~", number) }.into_bytes());
+	}
+
+	// modified keys that would be ambiguous ('CSI 27;<modifier>;<character> ~'), level 1 leaves out keys with control codes and alt+key
+	if modes.modify_other_keys > 0 && (event.ctrl || event.alt) {
+		let code = match event.key {
+			"Enter" => Some(13),
+			"Tab" => Some(9),
+			"Backspace" => Some(127),
+			"Escape" => Some(27),
+			key => single_char(key).map(|chr| chr as u32),
+		};
+		if let Some(code) = code {
+			let special = code < 32 || code == 127;
+			let legacy = !event.ctrl || (!event.shift && !special && single_char(event.key).and_then(|chr| control(chr, event.code)).is_some());
+			if modes.modify_other_keys >= 2 || !legacy {
+				return Some(format!("\x1b[27;{};{}~", modifier, code).into_bytes());
+			}
+		}
+	}
+
+	// other keys, alt sends ESC before them (alt as meta)
+	let mut bytes = match event.key {
+		"Enter" => vec![b'\r'],
+		"Tab" if event.shift => return Some(b"\x1b[Z".to_vec()),
+		"Tab" => vec![b'\t'],
+		"Backspace" => vec![if event.ctrl { 0x08 } else { 0x7f }],
+		"Escape" => vec![0x1b],
+		key => {
+			let Some(chr) = single_char(key) else { return None; }; // named keys without sequence
+			match control(chr, event.code) {
+				Some(byte) if event.ctrl => vec![byte],
+				_ => chr.to_string().into_bytes(), // characters without control code are sent as they are (like xterm does)
+			}
+		}
+	};
+	if event.alt { bytes.insert(0, 0x1b); }
+	return Some(bytes);
+}
+
+// kitty keyboard protocol ('CSI <code>:<shifted code> ; <modifier>:<event type> ; <text> u', cursor and function keys keep their legacy form with the same parameters)
+fn encode_kitty(event: &KEYS_event, modes: &KEYS_modes) -> Option<Vec<u8>> {
+
+	let flags = modes.kitty_flags;
+	let all = flags & KEYS_modes::KITTY_ALL_KEYS != 0;
+	let types = flags & KEYS_modes::KITTY_EVENT_TYPES != 0;
+	if event.kind == KEYS_kind::Release && !types { return None; }
+
+	let modifier = event.modifier();
+	let text = single_char(event.key);
+	let legacy_text = !all && text.is_some() && !event.ctrl && !event.alt; // plain typing stays plain
+	let legacy_special = !all && modifier == 1 && matches!(event.key, "Enter" | "Tab" | "Backspace");
+
+	// modifier parameter with event type ('5', '1:3' for release)
+	let parameter = match event.kind {
+		KEYS_kind::Press if modifier == 1 => String::new(),
+		KEYS_kind::Press => modifier.to_string(),
+		kind if types => format!("{}:{}", modifier, kind as u8),
+		_ => if modifier == 1 { String::new() } else { modifier.to_string() },
+	};
+
+	// keypad keys have own codes (digits only when all keys are reported, they are text otherwise)
+	if event.code.starts_with("Numpad") && (all || single_char(event.key).is_none()) {
+		if let Some(code) = keypad_code(event) {
+			if event.key == "Enter" && legacy_special { return if event.kind == KEYS_kind::Release { None } else { Some(b"\r".to_vec()) }; }
+			return Some(kitty_u(code.to_string(), &parameter, None));
+		}
+	}
+
+	// cursor, editing and function keys keep legacy form ('CSI 1;<modifier> A', 'CSI 5;<modifier> ~')
+	let legacy = match event.key {
+		"ArrowUp" => Some((1, 'A')),
+		"ArrowDown" => Some((1, 'B')),
+		"ArrowRight" => Some((1, 'C')),
+		"ArrowLeft" => Some((1, 'D')),
+		"Home" => Some((1, 'H')),
+		"End" => Some((1, 'F')),
+		"F1" => Some((1, 'P')),
+		"F2" => Some((1, 'Q')),
+		"F3" => Some((13, '~')),
+		"F4" => Some((1, 'S')),
+		"Insert" => Some((2, '~')),
+		"Delete" => Some((3, '~')),
+		"PageUp" => Some((5, '~')),
+		"PageDown" => Some((6, '~')),
+		"F5" => Some((15, '~')),
+		"F6" => Some((17, '~')),
+		"F7" => Some((18, '~')),
+		"F8" => Some((19, '~')),
+		"F9" => Some((20, '~')),
+		"F10" => Some((21, '~')),
+		"F11" => Some((23, '~')),
+		"F12" => Some((24, '~')),
+		_ => None,
+	};
+	if let Some((number, end)) = legacy {
+		return Some(
+			if !parameter.is_empty() { format!("\x1b[{};{}{}", number, parameter, end) }
+			else if end != '~' && modes.application_cursor && !all && number == 1 && !event.key.starts_with('F') { format!("\x1bO{}", end) }
+			else if end != '~' { format!("\x1b[{}", end) }
+			else { format!("\x1b[{}~", number) }
+			.into_bytes()
+		);
+	}
+
+	// other functional keys
+	let code = match event.key {
+		"Escape" => Some(27),
+		"Enter" => Some(13),
+		"Tab" => Some(9),
+		"Backspace" => Some(127),
+		"CapsLock" => Some(57358),
+		"ScrollLock" => Some(57359),
+		"NumLock" => Some(57360),
+		"PrintScreen" => Some(57361),
+		"Pause" => Some(57362),
+		"ContextMenu" => Some(57363),
+		key => key.strip_prefix('F').and_then(|n| n.parse::<u32>().ok()).filter(|n| (13..=35).contains(n)).map(|n| 57376 + n - 13), // F13-F35
+	};
+	let code = code.or(match event.code { // modifiers themselves (only reported with all keys)
+		"ShiftLeft" => Some(57441),
+		"ControlLeft" => Some(57442),
+		"AltLeft" => Some(57443),
+		"ShiftRight" => Some(57447),
+		"ControlRight" => Some(57448),
+		"AltRight" => Some(57449),
+		_ => None,
+	});
+	if let Some(code) = code {
+		if legacy_special {
+			return if event.kind == KEYS_kind::Release { None } else { encode(event, &KEYS_modes { kitty_flags: 0, ..*modes }) };
+		}
+		if matches!(code, 57358..=57360 | 57441..) && !all { return None; } // lock and modifier keys
+		return Some(kitty_u(code.to_string(), &parameter, None));
+	}
+
+	// text keys by their unshifted character
+	let chr = text?;
+	if legacy_text {
+		return if event.kind == KEYS_kind::Release { None } else { Some(chr.to_string().into_bytes()) };
+	}
+	let base = base_char(chr, event);
+	let mut key = (base as u32).to_string();
+	if flags & KEYS_modes::KITTY_ALTERNATE_KEYS != 0 && event.shift && chr != base {
+		key.push_str(&format!(":{}", chr as u32));
+	}
+	let associated = if flags & KEYS_modes::KITTY_TEXT != 0 && all && !event.ctrl && !event.alt && event.kind != KEYS_kind::Release { Some(chr as u32) } else { None }; // no text on release
+	return Some(kitty_u(key, &parameter, associated));
+}
+
+fn kitty_u(key: String, parameter: &str, text: Option<u32>) -> Vec<u8> {
+	match text {
+		Some(text) => format!("\x1b[{};{};{}u", key, if parameter.is_empty() { "1" } else { parameter }, text),
+		None if parameter.is_empty() => format!("\x1b[{}u", key),
+		None => format!("\x1b[{};{}u", key, parameter),
+	}.into_bytes()
+}
+
+// kitty code of keypad key (numbers from the kitty private use area)
+fn keypad_code(event: &KEYS_event) -> Option<u32> {
+	let code = match (event.code, event.key) {
+		(_, "ArrowLeft") => 57417,
+		(_, "ArrowRight") => 57418,
+		(_, "ArrowUp") => 57419,
+		(_, "ArrowDown") => 57420,
+		(_, "PageUp") => 57421,
+		(_, "PageDown") => 57422,
+		(_, "Home") => 57423,
+		(_, "End") => 57424,
+		(_, "Insert") => 57425,
+		(_, "Delete") => 57426,
+		(_, "Clear") => 57427, // begin (5 without num lock)
+		("NumpadDecimal", _) => 57409,
+		("NumpadDivide", _) => 57410,
+		("NumpadMultiply", _) => 57411,
+		("NumpadSubtract", _) => 57412,
+		("NumpadAdd", _) => 57413,
+		("NumpadEnter", _) => 57414,
+		("NumpadEqual", _) => 57415,
+		("NumpadComma", _) => 57416,
+		(code, _) => 57399 + code.strip_prefix("Numpad")?.parse::<u32>().ok().filter(|digit| *digit <= 9)?,
+	};
+	return Some(code);
+}
+
+// key without shift (layout gives it for letters, other keys are taken as on us keyboard)
+fn base_char(chr: char, event: &KEYS_event) -> char {
+	if !event.shift { return chr; }
+	if chr.is_alphabetic() { return chr.to_lowercase().next().unwrap_or(chr); }
+	match event.code {
+		"Digit0" => '0', "Digit1" => '1', "Digit2" => '2', "Digit3" => '3', "Digit4" => '4',
+		"Digit5" => '5', "Digit6" => '6', "Digit7" => '7', "Digit8" => '8', "Digit9" => '9',
+		"Minus" => '-', "Equal" => '=', "BracketLeft" => '[', "BracketRight" => ']', "Backslash" => '\\',
+		"Semicolon" => ';', "Quote" => '\'', "Backquote" => '`', "Comma" => ',', "Period" => '.', "Slash" => '/',
+		_ => chr,
+	}
+}
+
+fn single_char(key: &str) -> Option<char> { // key names are longer than one character
+	let mut chars = key.chars();
+	match (chars.next(), chars.next()) {
+		(Some(chr), None) => Some(chr),
+		_ => None,
+	}
+}
+
+// ctrl+character, letters are taken from the layout (or from the physical key for non latin layouts)
+fn control(chr: char, code: &str) -> Option<u8> {
+	let chr = match code.strip_prefix("Key") {
+		Some(letter) if !chr.is_ascii() => letter.chars().next().unwrap_or(chr),
+		_ => chr,
+	};
+	match chr.to_ascii_lowercase() {
+		letter @ 'a'..='z' => Some(letter as u8 - b'a' + 1),
+		'@' | ' ' | '2' => Some(0x00),
+		'[' | '3' => Some(0x1b),
+		'\\' | '4' => Some(0x1c),
+		']' | '5' => Some(0x1d),
+		'^' | '~' | '6' => Some(0x1e),
+		'_' | '-' | '/' | '7' => Some(0x1f),
+		'?' | '8' => Some(0x7f),
+		_ => None,
+	}
+}
+
+
+// bytes for pasted text, control characters are dropped (so the text cant end the bracket or run escape sequences) and newlines are sent as Enter
+pub fn paste(text: &str, modes: &KEYS_modes) -> Vec<u8> {
+	let text = text.replace("\r\n", "\r").replace('\n', "\r");
+	let text: String = text.chars().filter(|chr| !chr.is_control() || matches!(chr, '\t' | '\r')).collect();
+	if modes.bracketed_paste { return format!("\x1b[200~{}\x1b[201~", text).into_bytes(); }
+	return text.into_bytes();
+}
+
+// text that would run commands (or do something else than typing) when pasted without bracketed paste
+pub fn paste_unsafe(text: &str) -> bool {
+	text.chars().any(|chr| chr.is_control() && chr != '\t')
+}
+
+
+// bytes for mouse event in current tracking mode and encoding, None when the application doesnt want it
+pub fn encode_mouse(event: &KEYS_mouse, modes: &KEYS_modes) -> Option<Vec<u8>> {
+
+	let wheel = event.button >= 64;
+	let wanted = match (modes.mouse_tracking, event.action) {
+		(9, KEYS_mouse_action::Press) => !wheel, // X10 reports only button presses (without modifiers)
+		(1000 | 1002 | 1003, KEYS_mouse_action::Press) => true,
+		(1000 | 1002 | 1003, KEYS_mouse_action::Release) => !wheel,
+		(1002, KEYS_mouse_action::Move) => event.button != 3, // dragging only
+		(1003, KEYS_mouse_action::Move) => true,
+		_ => false,
+	};
+	if !wanted { return None; }
+
+	// button code with modifiers (shift 4, alt 8, ctrl 16) and motion (32)
+	let sgr = modes.mouse_encoding == 1006;
+	let mut code = if event.action == KEYS_mouse_action::Release && !sgr { 3 } else { event.button as usize }; // release of any button is 3 without SGR
+	if modes.mouse_tracking != 9 {
+		code += 4 * event.shift as usize + 8 * event.alt as usize + 16 * event.ctrl as usize;
+	}
+	if event.action == KEYS_mouse_action::Move { code += 32; }
+	let (x, y) = (event.column + 1, event.row + 1);
+
+	return match modes.mouse_encoding {
+		1006 => Some(format!("\x1b[<{};{};{}{}", code, x, y, if event.action == KEYS_mouse_action::Release { 'm' } else { 'M' }).into_bytes()),
+		1015 => Some(format!("\x1b[{};{};{}M", code + 32, x, y).into_bytes()),
+		1005 => { // values as utf-8 characters (up to 2015)
+			let mut bytes = b"\x1b[M".to_vec();
+			for value in [code, x, y] {
+				let chr = char::from_u32(value as u32 + 32).filter(|_| value + 32 < 0x800)?;
+				bytes.extend_from_slice(chr.encode_utf8(&mut [0; 4]).as_bytes());
+			}
+			Some(bytes)
+		}
+		_ => { // values as single bytes (positions over 223 cant be reported)
+			if x > 223 || y > 223 { return None; }
+			Some(vec![0x1b, b'[', b'M', (code + 32) as u8, (x + 32) as u8, (y + 32) as u8])
+		}
+	};
+}
+
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,174 @@
+// key encoder unit tests (bytes sent for given KeyboardEvent)
+
+use super::*;
+
+// modifiers as in the page ('S' shift, 'A' alt, 'C' ctrl), 'R' for repeat and 'U' for release
+fn key(code: &str, key: &str, modifiers: &str) -> String {
+	key_in(code, key, modifiers, &KEYS_modes::default())
+}
+
+fn key_in(code: &str, key: &str, modifiers: &str, modes: &KEYS_modes) -> String {
+	let event = KEYS_event { code, key, shift: modifiers.contains('S'), alt: modifiers.contains('A'), ctrl: modifiers.contains('C'), kind: if modifiers.contains('U') { KEYS_kind::Release } else if modifiers.contains('R') { KEYS_kind::Repeat } else { KEYS_kind::Press } };
+	match encode(&event, modes) {
+		Some(bytes) => String::from_utf8_lossy(&bytes).replace('\x1b', "^["),
+		None => "none".to_string(),
+	}
+}
+
+#[test]
+fn characters() {
+	assert_eq!(key("KeyA", "a", ""), "a");
+	assert_eq!(key("KeyA", "A", "S"), "A");
+	assert_eq!(key("KeyE", "é", ""), "é");
+	assert_eq!(key("Space", " ", ""), " ");
+	assert_eq!(key("KeyX", "x", "A"), "^[x"); // alt as meta
+	assert_eq!(key("Enter", "Enter", ""), "\r");
+	assert_eq!(key("Enter", "Enter", "A"), "^[\r");
+	assert_eq!(key("Backspace", "Backspace", ""), "\x7f");
+	assert_eq!(key("Backspace", "Backspace", "C"), "\x08");
+	assert_eq!(key("Tab", "Tab", ""), "\t");
+	assert_eq!(key("Tab", "Tab", "S"), "^[[Z");
+	assert_eq!(key("Escape", "Escape", ""), "^[");
+	assert_eq!(key("AudioVolumeUp", "AudioVolumeUp", ""), "none");
+}
+
+#[test]
+fn control_characters() {
+	assert_eq!(key("KeyC", "c", "C"), "\x03");
+	assert_eq!(key("KeyC", "C", "CS"), "\x03");
+	assert_eq!(key("KeyC", "с", "C"), "\x03"); // cyrillic layout
+	assert_eq!(key("KeyC", "c", "CA"), "^[\x03");
+	assert_eq!(key("Space", " ", "C"), "\x00");
+	assert_eq!(key("Digit2", "@", "CS"), "\x00");
+	assert_eq!(key("BracketLeft", "[", "C"), "^[");
+	assert_eq!(key("Backslash", "\\", "C"), "\x1c");
+	assert_eq!(key("BracketRight", "]", "C"), "\x1d");
+	assert_eq!(key("Digit6", "^", "CS"), "\x1e");
+	assert_eq!(key("Slash", "/", "C"), "\x1f");
+	assert_eq!(key("Period", ".", "C"), "."); // no control code
+}
+
+#[test]
+fn cursor_and_function_keys() {
+	let application = KEYS_modes { application_cursor: true, ..KEYS_modes::default() };
+	assert_eq!(key("ArrowUp", "ArrowUp", ""), "^[[A");
+	assert_eq!(key_in("ArrowUp", "ArrowUp", "", &application), "^[OA");
+	assert_eq!(key("ArrowRight", "ArrowRight", "C"), "^[[1;5C");
+	assert_eq!(key_in("ArrowRight", "ArrowRight", "C", &application), "^[[1;5C");
+	assert_eq!(key("ArrowLeft", "ArrowLeft", "SA"), "^[[1;4D");
+	assert_eq!(key("Home", "Home", ""), "^[[H");
+	assert_eq!(key("End", "End", "S"), "^[[1;2F");
+	assert_eq!(key("F1", "F1", ""), "^[OP");
+	assert_eq!(key("F4", "F4", "C"), "^[[1;5S");
+	assert_eq!(key("F5", "F5", ""), "^[[15~");
+	assert_eq!(key("F12", "F12", "S"), "^[[24;2~");
+	assert_eq!(key("Delete", "Delete", ""), "^[[3~");
+	assert_eq!(key("PageDown", "PageDown", "C"), "^[[6;5~");
+	assert_eq!(key("F13", "F13", ""), "none");
+}
+
+#[test]
+fn keypad() {
+	let application = KEYS_modes { application_keypad: true, ..KEYS_modes::default() };
+	assert_eq!(key("Numpad5", "5", ""), "5");
+	assert_eq!(key_in("Numpad5", "5", "", &application), "^[Ou");
+	assert_eq!(key_in("NumpadEnter", "Enter", "", &application), "^[OM");
+	assert_eq!(key_in("NumpadAdd", "+", "", &application), "^[Ok");
+	assert_eq!(key_in("Numpad8", "ArrowUp", "", &application), "^[[A"); // num lock off
+}
+
+#[test]
+fn modify_other_keys() {
+	let level1 = KEYS_modes { modify_other_keys: 1, ..KEYS_modes::default() };
+	let level2 = KEYS_modes { modify_other_keys: 2, ..KEYS_modes::default() };
+	assert_eq!(key_in("KeyA", "a", "C", &level1), "\x01"); // has control code
+	assert_eq!(key_in("KeyA", "A", "CS", &level1), "^[[27;6;65~");
+	assert_eq!(key_in("Enter", "Enter", "C", &level1), "^[[27;5;13~");
+	assert_eq!(key_in("Digit1", "1", "C", &level1), "^[[27;5;49~");
+	assert_eq!(key_in("KeyX", "x", "A", &level1), "^[x");
+	assert_eq!(key_in("KeyA", "a", "C", &level2), "^[[27;5;97~");
+	assert_eq!(key_in("KeyX", "x", "A", &level2), "^[[27;3;120~");
+	assert_eq!(key_in("KeyA", "a", "", &level2), "a");
+	assert_eq!(key_in("ArrowUp", "ArrowUp", "C", &level2), "^[[1;5A");
+}
+
+#[test]
+fn kitty() {
+	let disambiguate = KEYS_modes { kitty_flags: KEYS_modes::KITTY_DISAMBIGUATE, ..KEYS_modes::default() };
+	assert_eq!(key_in("KeyA", "a", "", &disambiguate), "a");
+	assert_eq!(key_in("KeyA", "A", "S", &disambiguate), "A");
+	assert_eq!(key_in("KeyI", "i", "C", &disambiguate), "^[[105;5u"); // not tab
+	assert_eq!(key_in("Tab", "Tab", "", &disambiguate), "\t");
+	assert_eq!(key_in("Tab", "Tab", "S", &disambiguate), "^[[9;2u");
+	assert_eq!(key_in("Escape", "Escape", "", &disambiguate), "^[[27u");
+	assert_eq!(key_in("Enter", "Enter", "", &disambiguate), "\r");
+	assert_eq!(key_in("KeyX", "x", "A", &disambiguate), "^[[120;3u");
+	assert_eq!(key_in("ArrowUp", "ArrowUp", "C", &disambiguate), "^[[1;5A");
+	assert_eq!(key_in("F3", "F3", "", &disambiguate), "^[[13~");
+	assert_eq!(key_in("Numpad8", "ArrowUp", "", &disambiguate), "^[[57419u");
+	assert_eq!(key_in("KeyA", "a", "U", &disambiguate), "none");
+	assert_eq!(key_in("ShiftLeft", "Shift", "S", &disambiguate), "none");
+
+	let types = KEYS_modes { kitty_flags: KEYS_modes::KITTY_DISAMBIGUATE | KEYS_modes::KITTY_EVENT_TYPES, ..KEYS_modes::default() };
+	assert_eq!(key_in("KeyI", "i", "CR", &types), "^[[105;5:2u");
+	assert_eq!(key_in("KeyI", "i", "CU", &types), "^[[105;5:3u");
+	assert_eq!(key_in("ArrowLeft", "ArrowLeft", "U", &types), "^[[1;1:3D");
+	assert_eq!(key_in("KeyA", "a", "U", &types), "none"); // text was sent as text
+
+	let all = KEYS_modes { kitty_flags: KEYS_modes::KITTY_FLAGS, ..KEYS_modes::default() };
+	assert_eq!(key_in("KeyA", "a", "", &all), "^[[97;1;97u");
+	assert_eq!(key_in("KeyA", "A", "S", &all), "^[[97:65;2;65u");
+	assert_eq!(key_in("Digit1", "!", "S", &all), "^[[49:33;2;33u");
+	assert_eq!(key_in("KeyA", "a", "U", &all), "^[[97;1:3u");
+	assert_eq!(key_in("Enter", "Enter", "", &all), "^[[13u");
+	assert_eq!(key_in("ShiftLeft", "Shift", "S", &all), "^[[57441;2u");
+	assert_eq!(key_in("Numpad5", "5", "", &all), "^[[57404u");
+}
+
+#[test]
+fn paste() {
+	let bracketed = KEYS_modes { bracketed_paste: true, ..KEYS_modes::default() };
+	let text = "echo a\r\necho\tb\n\x1b[201~rm -rf x\x07\u{9b}";
+	assert_eq!(String::from_utf8_lossy(&super::paste(text, &KEYS_modes::default())), "echo a\recho\tb\r[201~rm -rf x");
+	assert_eq!(String::from_utf8_lossy(&super::paste("ls ~\n", &bracketed)), "\x1b[200~ls ~\r\x1b[201~");
+	assert!(paste_unsafe("make\n"));
+	assert!(paste_unsafe("a\x1bb"));
+	assert!(!paste_unsafe("git log --oneline\tčšž"));
+}
+
+fn mouse_in(action: KEYS_mouse_action, button: u8, row: usize, column: usize, modifiers: &str, tracking: usize, encoding: usize) -> String {
+	let event = KEYS_mouse { action, button, row, column, shift: modifiers.contains('S'), alt: modifiers.contains('A'), ctrl: modifiers.contains('C') };
+	let modes = KEYS_modes { mouse_tracking: tracking, mouse_encoding: encoding, ..KEYS_modes::default() };
+	match encode_mouse(&event, &modes) {
+		Some(bytes) => String::from_utf8_lossy(&bytes).replace('\x1b', "^["),
+		None => "none".to_string(),
+	}
+}
+
+#[test]
+fn mouse() {
+	use KEYS_mouse_action::*;
+	assert_eq!(mouse_in(Press, 0, 0, 0, "", 0, 0), "none");
+
+	// X10 only presses without modifiers
+	assert_eq!(mouse_in(Press, 0, 4, 9, "C", 9, 0), "^[[M *%");
+	assert_eq!(mouse_in(Release, 0, 4, 9, "", 9, 0), "none");
+	assert_eq!(mouse_in(Press, 64, 4, 9, "", 9, 0), "none");
+
+	// normal tracking reports release as button 3 and wheel
+	assert_eq!(mouse_in(Press, 2, 0, 0, "C", 1000, 0), "^[[M2!!");
+	assert_eq!(mouse_in(Release, 2, 0, 0, "", 1000, 0), "^[[M#!!");
+	assert_eq!(mouse_in(Press, 65, 1, 2, "", 1000, 0), "^[[Ma#\"");
+	assert_eq!(mouse_in(Move, 0, 1, 2, "", 1000, 0), "none");
+	assert_eq!(mouse_in(Press, 0, 0, 300, "", 1000, 0), "none"); // too far for bytes
+
+	// motion with button (1002) or any (1003)
+	assert_eq!(mouse_in(Move, 0, 1, 2, "", 1002, 1006), "^[[<32;3;2M");
+	assert_eq!(mouse_in(Move, 3, 1, 2, "", 1002, 1006), "none");
+	assert_eq!(mouse_in(Move, 3, 1, 2, "", 1003, 1006), "^[[<35;3;2M");
+
+	// encodings
+	assert_eq!(mouse_in(Release, 1, 9, 299, "A", 1000, 1006), "^[[<9;300;10m");
+	assert_eq!(mouse_in(Press, 0, 9, 299, "S", 1000, 1015), "^[[36;300;10M");
+	assert_eq!(mouse_in(Press, 0, 0, 299, "", 1000, 1005), "^[[M \u{14c}!");
+}
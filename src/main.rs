@@ -1,13 +1,12 @@
 use HUI::*;
 use std::thread;
 use std::time::{Duration, Instant};
-use std::collections::HashMap;
 use std::env;
 use std::fs::File;
 use std::io::{self, BufReader, Read, Seek, SeekFrom};
 use std::io::BufRead;
-use std::cmp::min;
 use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
 
 #[cfg(target_os = "linux")]
 use std::os::unix::io::RawFd;
@@ -35,7 +34,7 @@ use windows::Win32::Storage::FileSystem::{ReadFile, WriteFile};
 #[cfg(target_os = "windows")]
 use windows::Win32::System::Console::{ClosePseudoConsole, CreatePseudoConsole, ResizePseudoConsole, HPCON};
 #[cfg(target_os = "windows")]
-use windows::Win32::System::Pipes::{CreatePipe, PeekNamedPipe};
+use windows::Win32::System::Pipes::CreatePipe;
 #[cfg(target_os = "windows")]
 use windows::Win32::System::Threading::{CreateProcessW, DeleteProcThreadAttributeList, InitializeProcThreadAttributeList, UpdateProcThreadAttribute, PROCESS_INFORMATION, STARTUPINFOEXW, EXTENDED_STARTUPINFO_PRESENT, PROC_THREAD_ATTRIBUTE_PSEUDOCONSOLE, GetExitCodeProcess};
 
@@ -61,74 +60,316 @@ macro_rules! eprintln {
 }
 
 
+mod config;
+mod theme;
+mod parser;
+mod buff;
+mod keys;
+
+use theme::PALETTE;
+use parser::PARSER;
+use buff::{BUFF, BUFF_clipboard};
+
+
 struct OPTIONS {
     shell: String, // your shell or any other command (with or without arguments but no bash operators; if you want bash to create console pauser or pipes or whatever, just use sh -c)
 	shell_args: Vec<String>,  // arguments for the shell (if loaded from the config file, args are part of the shell, so just parse them out)
     term: String, // terminal type to be advertised by termila to the shell (possible values: dumb, vt100, xterm, xterm-265color); linux-only
-	max_buff_size: usize,
-    /*
+	working_directory: String, // working directory of the shell (empty means same as termila)
+	title: String, // window title (while the application doesnt set any)
+	class: String, // window class (used by window managers to group/match windows)
+	geometry: Option<(u16, u16)>, // initial terminal size (rows, columns), window is resized to fit it
+	hold: bool, // keep window open after the shell exits
+	process_title: bool, // show name of the foreground process in tab label and window title when the application didnt set any title
+	clipboard_read: String, // applications reading clipboard (OSC 52): allow, deny or prompt
+	paste_warning: bool, // ask before pasting text with newlines or control characters into application without bracketed paste
+	link_handler: String, // command opening ctrl+clicked links ('{}' is replaced by the link and '{kind}' by url/file/hash, link is appended when there is no '{}'), empty means system default
+	fps: u8, // terminal content updates per second
+	max_buff_size: usize, // max number of scrollback lines
+	font: String, // css font family of terminal text
+	font_size: u16, // font size in px
+	cursor_shape: String, // block, underline or bar (until the application sets its own)
+	cursor_blink: u16, // blink interval in ms (0 means steady, even when the application asks for blinking)
+	cursor_color: String, // css color of the cursor (empty means the one of the theme)
 	ai_url: String, // url of OpenAI API server
     ai_key: String, // OpenAI API key
     ai_model: String, // OpenAI API model
     ai_prompt: String, // OpenAI API system prompt
-	*/
-    // TODO: color_override: String, // CSS function(s) to modify colors
+    palette: PALETTE, // terminal colors (builtin theme name or theme file - alacritty toml, base16 yaml or iterm2 itermcolors)
     // TODO: bell_audio: String, // bell audio file
     saved_commands_file: String, // file with saved commands
 	history_file: String, // file with shell history (to allow history modifications)
     // TODO: shell profiles / any shortcuts
 }
 impl OPTIONS {
-	fn new() -> Self { // no config file, default config
+	
+	// option names (same in config file and in environment variables as 'TERMILA_<NAME>')
+	const KEYS: &'static [&'static str] = &["shell", "args", "term", "fps", "scrollback", "process_title", "clipboard_read", "paste_warning", "link_handler", "font", "font_size", "cursor_shape", "cursor_blink", "cursor_color", "theme", "ai_url", "ai_key", "ai_model", "ai_prompt", "saved_commands", "history"];
+	
+	const HELP: &'static str = "usage: termila [options] [-e | --] [command [arguments...]]
+
+options:
+  -e, --command CMD [ARGS...]    run CMD with ARGS instead of the shell (takes all remaining arguments)
+  --working-directory DIR        start the command in DIR
+  --title TITLE                  window title (while the application doesnt set any)
+  --config FILE                  config file (default: $XDG_CONFIG_HOME/termila/config.toml)
+  --term TERM                    terminal type advertised to the command (TERM variable)
+  --geometry COLSxROWS           initial terminal size (ie. 120x40)
+  --hold                         keep the window open after the command exits
+  --class CLASS                  window class
+  -v, --version                  print version and exit
+  -h, --help                     print this help and exit
+
+everything after -e or -- (or the first argument that is not an option) is the command to run";
+	
+	fn new() -> Result<Self, String> { // default config < config file < environment variables < command line
 		
-		// shell, shell_args
+		// command line is parsed first (it can change the config file), but applied last
 		
-		let mut _args: Vec<String> = env::args().collect();
+		let (cli, command) = OPTIONS::parse_args(&env::args().skip(1).collect::<Vec<String>>())?;
+		let cli_value = |name: &str| cli.iter().rev().find(|(n, _)| n == name).map(|(_, v)| v.clone());
 		
-		let mut shell: String;
-		let mut shell_args: Vec<String> = vec![];
-
-		if _args.len() >= 2 {
-			shell = _args[1].clone();
-			shell_args = _args[2..].to_vec();
-		}
-		else 
-		{
+		
+		// defaults
+		
+		let mut options = Self {
 			#[cfg(target_os = "linux")]
-			{ shell = "bash".to_string(); /* = std::env::var("SHELL").unwrap_or_else(|_| "bash".to_string()); */ }
+			shell: "bash".to_string(), /* = std::env::var("SHELL").unwrap_or_else(|_| "bash".to_string()); */
 			#[cfg(target_os = "windows")]
-			{ shell = "cmd.exe".to_string(); }
+			shell: "cmd.exe".to_string(),
+			shell_args: vec![],
+			term: std::env::var("TERM").unwrap_or_else(|_| "xterm".to_string()),
+			working_directory: "".to_string(),
+			title: "termila".to_string(),
+			class: "termila".to_string(),
+			geometry: None,
+			hold: false,
+			process_title: false,
+			clipboard_read: "prompt".to_string(),
+			paste_warning: true,
+			link_handler: "".to_string(),
+			fps: 5,
+			max_buff_size: 10000,
+			font: "monospace".to_string(),
+			font_size: 14,
+			cursor_shape: "block".to_string(),
+			cursor_blink: 600,
+			cursor_color: "".to_string(),
+			ai_url: "http://127.0.0.1:8080/v1/chat/completions".to_string(), // 'https://api.openai.com/v1/chat/completions'
+			ai_key: "YOUR_API_KEY_HERE".to_string(),
+			ai_model: "gpt-4o-mini".to_string(),
+			ai_prompt: "You are a helpful assistant. \nYou help the user with terminal interaction by explaining commands, giving solutions to errors and evaluating safety of commands. \nAnswer shortly, dont use markdown.".to_string(),
+			palette: PALETTE::builtin("default").unwrap(),
+			saved_commands_file: "".to_string(),
+			history_file: "".to_string(),
+		};
+		let mut history_set = false;
+		
+		
+		// config file
+		
+		let config_explicit = cli_value("config");
+		if let Some(path) = config_explicit.clone().or_else(OPTIONS::config_file) {
+			match std::fs::read_to_string(&path) {
+				Ok(text) => {
+					let values = config::parse_toml(&text).map_err(|e| format!("config file '{}': {}", path, e))?;
+					for (key, value) in values {
+						history_set |= key == "history";
+						options.set(&key, value).map_err(|e| format!("config file '{}': {}", path, e))?;
+					}
+				}
+				Err(e) if e.kind() == io::ErrorKind::NotFound && config_explicit.is_none() => {} // no default config file is fine
+				Err(e) => return Err(format!("config file '{}': {}", path, e)),
+			}
+		}
+		
+		
+		// environment variables
+		
+		for key in OPTIONS::KEYS {
+			let name = format!("TERMILA_{}", key.to_uppercase());
+			if let Ok(value) = env::var(&name) {
+				history_set |= *key == "history";
+				options.set(key, config::CONFIG_value::String(value)).map_err(|e| format!("environment variable {}: {}", name, e))?;
+			}
 		}
 		
 		
-		// term
+		// command line
 		
-		let term = std::env::var("TERM").unwrap_or_else(|_| "xterm".to_string());
+		for (name, value) in cli {
+			match name.as_str() {
+				"working-directory" => options.working_directory = value,
+				"title" => options.title = value,
+				"term" => options.term = value,
+				"class" => options.class = value,
+				"hold" => options.hold = true,
+				"geometry" => {
+					let size = value.split_once('x').and_then(|(c, r)| Some((r.trim().parse::<u16>().ok()?, c.trim().parse::<u16>().ok()?)));
+					options.geometry = Some(size.filter(|(r, c)| *r > 0 && *c > 0).ok_or(format!("invalid geometry '{}' (expected COLSxROWS, ie. 120x40)", value))?);
+				}
+				_ => {} // config (already used)
+			}
+		}
+		if !command.is_empty() {
+			options.shell = command[0].clone();
+			options.shell_args = command[1..].to_vec();
+		}
 		
-		// max_buff_size
-		let max_buff_size = usize::MAX;
+		
+		// history_file default depends on the shell
+		
+		if !history_set {
+			#[cfg(target_os = "linux")]
+			{ options.history_file = std::env::var("HOME").unwrap_or_default()+"/."+std::path::Path::new(&options.shell).file_name().and_then(|n| n.to_str()).unwrap_or("")+"_history"; }
+			#[cfg(target_os = "windows")]
+			{ options.history_file = "".to_string(); } // windows cmd.exe doesnt store history
+		}
 		
 		
-		// saved_commands_file, history_file
+		return Ok(options);
+	}
+	
+	fn parse_args(args: &[String]) -> Result<(Vec<(String, String)>, Vec<String>), String> { // returns options (name without dashes, value) and the command
 		
-		let saved_commands_file = std::env::var("TERMILA_SAVED_COMMANDS").unwrap_or_default();
+		let mut options = vec![];
+		let mut i = 0;
+		while i < args.len() {
+			let arg = &args[i];
+			
+			// rest of the arguments is the command
+			if arg == "--" || arg == "-e" || arg == "--command" {
+				if i+1 >= args.len() { return Err(format!("option '{}' requires a command", arg)); }
+				return Ok((options, args[i+1..].to_vec()));
+			}
+			if !arg.starts_with('-') { // first positional argument (ie. 'termila bash -l')
+				return Ok((options, args[i..].to_vec()));
+			}
+			
+			// '--name value' or '--name=value'
+			let (name, inline_value) = match arg.split_once('=') {
+				Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+				_ => (arg.as_str(), None),
+			};
+			match name {
+				"-h" | "--help" => {
+					println!("{}", OPTIONS::HELP);
+					std::process::exit(0);
+				}
+				"-v" | "--version" => {
+					println!("termila {}", env!("CARGO_PKG_VERSION"));
+					std::process::exit(0);
+				}
+				"--hold" => {
+					options.push(("hold".to_string(), "".to_string()));
+				}
+				"--working-directory" | "--title" | "--config" | "--term" | "--geometry" | "--class" => {
+					let value = match inline_value {
+						Some(value) => value,
+						None => { i += 1; args.get(i).cloned().ok_or(format!("option '{}' requires a value", name))? }
+					};
+					options.push((name[2..].to_string(), value));
+				}
+				_ => return Err(format!("unknown option '{}' (see termila --help)", arg)),
+			}
+			i += 1;
+		}
 		
-		let mut history_file: String;
+		return Ok((options, vec![]));
+	}
+	
+	fn config_file() -> Option<String> {
 		#[cfg(target_os = "linux")]
-		{ history_file = std::env::var("HOME").unwrap_or_default()+"/."+&shell+"_history"; }
+		{
+			let dir = match env::var("XDG_CONFIG_HOME") {
+				Ok(dir) if !dir.is_empty() => dir,
+				_ => env::var("HOME").ok()? + "/.config",
+			};
+			return Some(dir + "/termila/config.toml");
+		}
 		#[cfg(target_os = "windows")]
-		{ history_file = "".to_string(); } // windows cmd.exe doesnt store history
-
-
-		return Self {shell, shell_args, term, max_buff_size, saved_commands_file, history_file, };
+		{
+			return Some(env::var("APPDATA").ok()? + "\\termila\\config.toml");
+		}
+	}
+	
+	fn set(&mut self, key: &str, value: config::CONFIG_value) -> Result<(), String> {
+		use config::CONFIG_value;
+		
+		// values from environment are always strings, so numbers and lists are parsed from them
+		let string = || -> Result<String, String> {
+			value.as_str().map(|s| s.to_string()).ok_or(format!("option '{}' has to be string", key))
+		};
+		let number = |min: i64, max: i64| -> Result<i64, String> {
+			let n = match &value {
+				CONFIG_value::Integer(i) => *i,
+				CONFIG_value::String(s) => s.trim().parse::<i64>().map_err(|_| format!("option '{}' has to be number", key))?,
+				_ => return Err(format!("option '{}' has to be number", key)),
+			};
+			if n < min || n > max { return Err(format!("option '{}' has to be between {} and {}", key, min, max)); }
+			Ok(n)
+		};
+		let boolean = || -> Result<bool, String> {
+			match &value {
+				CONFIG_value::Boolean(b) => Ok(*b),
+				CONFIG_value::String(s) if s == "true" || s == "1" => Ok(true),
+				CONFIG_value::String(s) if s == "false" || s == "0" => Ok(false),
+				_ => Err(format!("option '{}' has to be true or false", key)),
+			}
+		};
+		
+		match key {
+			"shell" => self.shell = string()?,
+			"args" => self.shell_args = match &value {
+				CONFIG_value::Array(items) => items.iter().map(|i| i.as_str().map(|s| s.to_string())).collect::<Option<Vec<String>>>().ok_or(format!("option '{}' has to be list of strings", key))?,
+				CONFIG_value::String(s) => s.split_whitespace().map(|s| s.to_string()).collect(),
+				_ => return Err(format!("option '{}' has to be list of strings", key)),
+			},
+			"term" => self.term = string()?,
+			"fps" => self.fps = number(1, 240)? as u8,
+			"scrollback" => self.max_buff_size = number(0, i64::MAX)? as usize,
+			"process_title" => self.process_title = boolean()?,
+			"clipboard_read" => self.clipboard_read = match string()?.as_str() {
+				value @ ("allow" | "deny" | "prompt") => value.to_string(),
+				_ => return Err(format!("option '{}' has to be allow, deny or prompt", key)),
+			},
+			"paste_warning" => self.paste_warning = boolean()?,
+			"link_handler" => self.link_handler = string()?,
+			"font" => self.font = string()?,
+			"font_size" => self.font_size = number(1, 500)? as u16,
+			"cursor_shape" => self.cursor_shape = match string()?.as_str() {
+				value @ ("block" | "underline" | "bar") => value.to_string(),
+				_ => return Err(format!("option '{}' has to be block, underline or bar", key)),
+			},
+			"cursor_blink" => self.cursor_blink = number(0, 10000)? as u16,
+			"cursor_color" => self.cursor_color = string()?,
+			"theme" => self.palette = PALETTE::load(&string()?)?,
+			"ai_url" => self.ai_url = string()?,
+			"ai_key" => self.ai_key = string()?,
+			"ai_model" => self.ai_model = string()?,
+			"ai_prompt" => self.ai_prompt = string()?,
+			"saved_commands" => self.saved_commands_file = string()?,
+			"history" => self.history_file = string()?,
+			_ => return Err(format!("unknown option '{}' (valid options are: {})", key, OPTIONS::KEYS.join(", "))),
+		}
+		return Ok(());
 	}
+	
 }
 
 
 struct UI {
 	webview: HUI::WebView,
 	next_update: std::time::Instant,
-	tab: Arc<Mutex<TAB<'static>>>, // TODO: avoid static lifetime
+	tabs: Arc<Mutex<Vec<TAB>>>, // in tab bar order
+	active_tab: Arc<Mutex<usize>>, // index into tabs (always lock tabs first)
+	tab_requests: Arc<Mutex<Vec<Vec<String>>>>, // tab actions from tab bar and shortcuts, done in handle() (HUI calls are not allowed within call_native)
+	last_id: usize, // last tab or pane id
+	wake: Receiver<()>, // pty reader threads signal new output
+	wake_sender: SyncSender<()>,
+	busy: bool, // some pane didnt process all of its output yet
+	window_title: String, // currently displayed
+	window_focused: bool, // webview window has focus (focus events go only to the focused pane of the active tab)
 	options: OPTIONS,
 }
 impl UI {
@@ -136,14 +377,26 @@ impl UI {
 	fn new(options: OPTIONS) -> Self {
 		
 		// UI webview window
+		// TODO: HUI has no api for window class, so at least X11 resource name is set (only for the window, not inherited by the shell)
+		#[cfg(target_os = "linux")]
+		env::set_var("RESOURCE_NAME", &options.class);
 		let webview = HUI::WebView::new();
+		#[cfg(target_os = "linux")]
+		env::remove_var("RESOURCE_NAME");
 		//webview.hui_tweaks();
 		
-		// initialize terminal tab (we need more references for callbacks) - only one since there is no support for multiple yet
-		let mut tab = TAB::new(&options).unwrap();
-		let mut tab = Arc::new(Mutex::new(tab));
-		let tab_cb1 = tab.clone(); // tab for keypress
-		let tab_cb2 = tab.clone(); // tab for size changes
+		// terminal tabs (we need more references for callbacks), the first one is opened once UI is ready
+		let tabs: Arc<Mutex<Vec<TAB>>> = Arc::new(Mutex::new(vec![]));
+		let active_tab = Arc::new(Mutex::new(0));
+		let tab_requests: Arc<Mutex<Vec<Vec<String>>>> = Arc::new(Mutex::new(vec![]));
+		let tabs_cb1 = tabs.clone(); // tabs for keypress
+		let active_tab_cb1 = active_tab.clone();
+		let tabs_cb2 = tabs.clone(); // tabs for size changes
+		let tabs_cb3 = tabs.clone(); // tabs for clipboard replies
+		let tabs_cb4 = tabs.clone(); // tabs for key events
+		let active_tab_cb4 = active_tab.clone();
+		let tabs_cb5 = tabs.clone(); // tabs for mouse events
+		let tab_requests_cb = tab_requests.clone(); // tab and pane actions
 		
 		// set up initial value for UI update sheduler
 		let next_update = Instant::now();
@@ -152,73 +405,361 @@ impl UI {
         // setup UI
         webview.load_str(r#"<!DOCTYPE html>
         <html>
-			<head></head>
+			<head>
+				<style>
+					/* one element per terminal line, empty lines keep their height */
+					.console > span { display: block; }
+					.console > span:empty::before { content: '\200b'; }
+					.console.alternate > span:not(.alternate) { display: none; }
+
+					/* every tab is stacked over the others, only the active one is visible (hidden ones keep their size) */
+					#consoles { position: fixed; top: 0; left: 0; right: 0; bottom: 0; }
+					#consoles > .tab { position: absolute; top: 0; left: 0; right: 0; bottom: 0; display: flex; }
+					#consoles > .tab:not(.active) { visibility: hidden; }
+
+					/* panes inside tab are nested flex boxes (flex-grow is the split ratio), each pane has its own console */
+					.tab .split, .tab .console { flex: 1 1 0; min-width: 0; min-height: 0; }
+					.tab .split { display: flex; flex-direction: column; }
+					.tab .split.vertical { flex-direction: row; }
+					.tab .divider { flex: 0 0 4px; background-color: var(--termila_selection); cursor: row-resize; }
+					.tab .split.vertical > .divider { cursor: col-resize; }
+					.tab:has(.split) .console:not(.focused) { opacity: 0.7; }
+					.console { margin: 0; overflow-x: hidden; overflow-y: auto; text-wrap: nowrap; -webkit-user-select: text; }
+
+					/* tab bar (only shown with more than one tab) */
+					#tabbar { position: fixed; top: 0; left: 0; right: 0; height: 28px; display: flex; overflow-x: auto; overflow-y: hidden; }
+					#tabbar:not(:has(button + button)) { display: none; }
+					body:has(#tabbar button + button) #consoles { top: 28px; }
+					#tabbar > button { min-width: unset; margin: 2px; padding: 0 8px; text-wrap: nowrap; opacity: 0.6; }
+					#tabbar > button.active { opacity: 1; }
+					#tabbar > button > span.label { display: inline-block; max-width: 300px; overflow: hidden; text-overflow: ellipsis; vertical-align: bottom; }
+					#tabbar > button > span.close { margin-left: 8px; }
+
+					/* question shown when application wants to read the clipboard */
+					#clipboard_prompt { position: fixed; bottom: 10px; left: 50%; transform: translateX(-50%); padding: 8px; background-color: var(--termila_selection); display: none; }
+					#clipboard_prompt.visible { display: block; }
+					#paste_prompt { position: fixed; bottom: 10px; left: 50%; transform: translateX(-50%); max-width: 80%; padding: 8px; background-color: var(--termila_selection); display: none; }
+					#paste_prompt.visible { display: block; }
+					#paste_prompt pre { max-height: 8em; overflow: hidden; margin: 4px 0; white-space: pre-wrap; }
+
+					/* default colors (palette variables are set from OPTIONS) */
+					body { background-color: var(--termila_bg); color: var(--termila_fg); }
+					.console ::selection { background-color: var(--termila_selection); }
+					.console a[data-link] { color: inherit; text-decoration: none; }
+					.console a[data-link]:hover { text-decoration: underline; cursor: pointer; }
+					
+					/* cursor by DECSCUSR shape in data-cursor (1, 2 block, 3, 4 underline, 5, 6 bar, odd ones blink), hollow in unfocused window or pane */
+					.console[data-cursor='1'] .cursor, .console[data-cursor='2'] .cursor { background-color: var(--termila_cursor); color: var(--termila_bg); }
+					.console[data-cursor='3'] .cursor, .console[data-cursor='4'] .cursor { box-shadow: inset 0 -2px var(--termila_cursor); }
+					.console[data-cursor='5'] .cursor, .console[data-cursor='6'] .cursor { box-shadow: inset 2px 0 var(--termila_cursor); }
+					.console[data-cursor='1'] .cursor, .console[data-cursor='3'] .cursor, .console[data-cursor='5'] .cursor { animation: var(--termila_cursor_blink); }
+					body.blurred .console[data-cursor] .cursor, .console[data-cursor]:not(.focused) .cursor { background-color: transparent; color: inherit; box-shadow: inset 0 0 0 1px var(--termila_cursor); animation: none; }
+					@keyframes termila_cursor_blink { 50% { background-color: transparent; color: inherit; box-shadow: none; } }
+					.console > span.prompt[data-exit] { box-shadow: inset 3px 0 0 var(--termila_color_2); } /* exit status of finished command (shell integration) */
+					.console > span.prompt[data-exit]:not([data-exit="0"]) { box-shadow: inset 3px 0 0 var(--termila_color_1); }
+
+					/* command blocks (shell integration): exit status and duration by the prompt, tools over it on hover */
+					.console > span.prompt[data-info]::after { content: attr(data-info); float: right; opacity: 0.6; }
+					#block_tools { position: fixed; display: none; gap: 2px; }
+					#block_tools.visible { display: flex; }
+					#block_tools > button { min-width: unset; padding: 0 6px; }
+					@keyframes termila_blink { 50% { opacity: 0; } }
+				</style>
+				<style id="termila_folds"></style> <!-- rules hiding folded command blocks (see termila_block_action) -->
+			</head>
             <body style="position: relative;">
 			
-				<!-- TERMINAL SPACE -->
-                <p id="console" onclick="document.querySelectorAll('#menu button:has(+ div.popup)').forEach(f=>f.dataset.checked='false');/*document.querySelectorAll('#menu div').forEach(f=>f.style.visibility='hidden');*/" style="-webkit-user-select: text; margin: 0;  text-wrap: nowrap;"></p>
+				<!-- TAB BAR -->
+				<div id="tabbar"></div>
+			
+				<!-- CLIPBOARD READ PROMPT (answered by onanswer set in termila_clipboard_read) -->
+				<!-- PASTE WARNING (shown by termila_paste_prompt) -->
+				<div id="paste_prompt">pasted text contains <span></span> and will run right away <pre></pre><button onmousedown="event.preventDefault()" onclick="this.parentElement.onanswer(true)">Paste</button> <button onmousedown="event.preventDefault()" onclick="this.parentElement.onanswer(false)">Cancel</button></div>
+				<div id="clipboard_prompt">application wants to read the clipboard <button onmousedown="event.preventDefault()" onclick="this.parentElement.onanswer(true)">Allow</button> <button onmousedown="event.preventDefault()" onclick="this.parentElement.onanswer(false)">Deny</button></div>
+			
+				<!-- COMMAND BLOCK TOOLS (for the block of hovered prompt, set in mouseover listener) -->
+				<div id="block_tools" onmousedown="event.preventDefault()" onclick="termila_block_action(event.target.dataset.action)"><button data-action="fold">fold</button><button data-action="copy">copy</button><button data-action="ai">AI</button></div>
+			
+				<!-- TERMINAL SPACE (one '<div class="tab">' per tab with one '<p class="console">' per pane) -->
+                <div id="consoles" onclick="document.querySelectorAll('#menu button:has(+ div.popup)').forEach(f=>f.dataset.checked='false');/*document.querySelectorAll('#menu div').forEach(f=>f.style.visibility='hidden');*/"></div>
 				<script>
 				
-					function term_type(what) {
-						
-						if (typeof what == 'object'){ // key event
+					// tab and pane shortcuts (capture phase, so they never get to the terminal)
+					document.addEventListener('keydown', function(event) {
+						const arrow = {ArrowLeft: 'left', ArrowRight: 'right', ArrowUp: 'up', ArrowDown: 'down'}[event.code];
+						let action = null;
+						if (event.altKey && !event.ctrlKey && !event.shiftKey && arrow) { action = ['focus', arrow]; }
+						else if (!event.ctrlKey || event.altKey) {return;}
+						else if (event.shiftKey && arrow) { action = ['resize', arrow]; }
+						else if (event.shiftKey && event.code == 'KeyE') { action = ['split', 'vertical']; }
+						else if (event.shiftKey && event.code == 'KeyO') { action = ['split', 'horizontal']; }
+						else if (event.shiftKey && event.code == 'KeyW') { action = ['close_pane']; }
+						else if (event.shiftKey && event.code == 'KeyT') { action = ['new']; }
+						else if (event.shiftKey && (event.code == 'KeyZ' || event.code == 'KeyX')) { termila_jump_prompt(event.code == 'KeyZ' ? -1 : 1); }
+						else if (event.shiftKey && event.code == 'KeyG') { termila_select_output(); }
+						else if (!event.shiftKey && (event.code == 'PageDown' || event.code == 'Tab')) { action = ['next']; }
+						else if (!event.shiftKey && event.code == 'PageUp' || event.shiftKey && event.code == 'Tab') { action = ['previous']; }
+						else if (event.shiftKey && event.code == 'PageDown') { action = ['move', '1']; }
+						else if (event.shiftKey && event.code == 'PageUp') { action = ['move', '-1']; }
+						else {return;}
+						event.preventDefault();
+						event.stopImmediatePropagation();
+						if (action != null) { tab_handle(...action); }
+					}, true);
+					
+					// prompts marked by shell integration (OSC 133) in the focused console
+					function termila_prompts() {
+						const c = document.querySelector('#consoles > .tab.active .console.focused');
+						if (!c || c.classList.contains('alternate')) {return [null, []];}
+						return [c, [...c.querySelectorAll(':scope > span.prompt')]];
+					}
+					function termila_jump_prompt(direction) { // scroll to previous (-1) or next (1) prompt
+						const [c, prompts] = termila_prompts();
+						const top = line => line.getBoundingClientRect().top - c.getBoundingClientRect().top;
+						const prompt = direction < 0 ? prompts.findLast(p => top(p) < -1) : prompts.find(p => top(p) > 1);
+						if (prompt) { c.scrollTop += top(prompt); }
+					}
+					function termila_select_output() { // select output of the command at the top of the console (or of the last one when scrolled to the bottom)
+						const [c, prompts] = termila_prompts();
+						if (!c) {return;}
+						const bottom = c.scrollTop + c.clientHeight >= c.scrollHeight - 1;
+						const prompt = bottom ? prompts[prompts.length - 2] : prompts.findLast(p => p.getBoundingClientRect().top <= c.getBoundingClientRect().top + 1);
+						const lines = prompt ? termila_block_output(prompt) : [];
+						if (!lines.length) {return;}
+						const range = document.createRange();
+						range.setStartBefore(lines[0]);
+						range.setEndAfter(lines[lines.length - 1]);
+						window.getSelection().removeAllRanges();
+						window.getSelection().addRange(range);
+					}
+					
+					// command blocks (lines of one command share data-block, its prompt line has the command column, exit status and duration)
+					const termila_folded = new Set(); // selectors of folded blocks
+					function termila_block_selector(prompt) { return '#' + prompt.parentElement.id + ' > span[data-block="' + prompt.dataset.block + '"]'; }
+					function termila_block_output(prompt) { // lines from the one marked as output start to the end of the block
+						const lines = [];
+						for (let line = prompt.nextElementSibling; line && line.dataset.block == prompt.dataset.block && !line.classList.contains('prompt'); line = line.nextElementSibling) { lines.push(line); }
+						const start = lines.findIndex(line => line.classList.contains('output'));
+						return start < 0 ? [] : lines.slice(start);
+					}
+					function termila_lines_text(lines) { return lines.map(line => line.textContent.replace(/\u00a0/g, ' ')).join('\n').replace(/\n+$/, ''); }
+					function termila_block_action(action) {
+						const tools = document.querySelector('#block_tools');
+						const prompt = document.querySelector('#' + tools.dataset.console + ' > span.prompt[data-block="' + tools.dataset.block + '"]'); // lines are replaced on updates, so it is looked up again
+						if (!action || !prompt) {return;}
+						if (action == 'fold') {
+							const selector = termila_block_selector(prompt);
+							if (!termila_folded.delete(selector)) { termila_folded.add(selector); }
+							document.querySelector('#termila_folds').textContent = [...termila_folded].map(s => s + ':not(.prompt) { display: none; } ' + s + '.prompt::after { content: attr(data-info) " (folded)"; }').join('\n');
+							tools.classList.remove('visible');
+						}
+						else if (action == 'copy') {
+							termila_clipboard_write(termila_lines_text(termila_block_output(prompt)));
+						}
+						else if (action == 'ai') { // command with its output becomes context of the question (like selected text)
+							const command = prompt.textContent.replace(/\u00a0/g, ' ').slice(prompt.dataset.command || 0);
+							document.querySelector('#menu button#ai').dataset.checked = 'true';
+							document.querySelector('div#ai input').focus();
+							document.querySelector('#consoles').dataset.selection = '$ ' + command + '\n' + termila_lines_text(termila_block_output(prompt));
+						}
+					}
+					document.addEventListener('mouseover', function(event) {
+						const tools = document.querySelector('#block_tools');
+						if (tools.contains(event.target)) {return;}
+						const prompt = event.target.closest('.console > span.prompt[data-info]');
+						tools.classList.toggle('visible', prompt != null);
+						if (!prompt) {return;}
+						const r = prompt.getBoundingClientRect();
+						tools.style.top = r.top + 'px';
+						tools.style.right = (document.documentElement.clientWidth - r.right) + 'px';
+						tools.dataset.console = prompt.parentElement.id;
+						tools.dataset.block = prompt.dataset.block;
+						tools.querySelector('[data-action="fold"]').textContent = termila_folded.has(termila_block_selector(prompt)) ? 'unfold' : 'fold';
+					});
+					document.addEventListener('scroll', () => document.querySelector('#block_tools').classList.remove('visible'), true);
+					
+					// clicking pane focuses it, dragging divider resizes neighbouring panes
+					document.addEventListener('mousedown', function(event) {
 						
-							if (document.activeElement.tagName != 'BODY'){return;} // allow interaction with other inputs too
+						const pane = event.target.closest('.console');
+						if (pane && !pane.classList.contains('focused')) { tab_handle('select_pane', pane.dataset.pane); }
 						
-							// TODO: other non letter characters
-							
-							if (what.ctrlKey && what.keyCode >= 65 && what.keyCode <= 90) { // ctrl a..z
-								if (what.ctrlKey && what.keyCode == 67 && window.getSelection().toString() != '') {return;} // allow ctrl c copy
-								if (what.ctrlKey && what.keyCode == 86) {return;} // allow ctrl v paste
-								term_type( what.keyCode-64 );
-								what.preventDefault();
-							}
-							
-							else if (what.keyCode == 38 && what.type == 'keydown') { // up
-								term_type(27);
-								term_type(91);
-								term_type(65);
-								what.preventDefault();
-							}
-							else if (what.keyCode == 40 && what.type == 'keydown') { // down
-								term_type(27);
-								term_type(91);
-								term_type(66);
-								what.preventDefault();
-							}
-							else if (what.keyCode == 39 && what.type == 'keydown') { // right
-								term_type(27);
-								term_type(91);
-								term_type(67);
-								what.preventDefault();
-							}
-							else if (what.keyCode == 37 && what.type == 'keydown') { // left
-								term_type(27);
-								term_type(91);
-								term_type(68);
-								what.preventDefault();
-							}
-							
-							else if (event.keyCode == 27) { // esc
-								term_type(27);
-								what.preventDefault();
-							}
-							
-							else if (event.keyCode == 9) { // tab
-								term_type(9);
-								what.preventDefault();
-							}
+						const divider = event.target.closest('.divider');
+						if (!divider) {return;}
+						event.preventDefault();
+						const split = divider.parentElement;
+						const first = divider.previousElementSibling;
+						const second = divider.nextElementSibling;
+						let ratio = null;
 						
-							else if (event.keyCode == 8) { // backspace
-								term_type(8);
-								what.preventDefault();
+						function move(event) {
+							const r = split.getBoundingClientRect();
+							ratio = split.classList.contains('vertical') ? (event.clientX - r.left) / r.width : (event.clientY - r.top) / r.height;
+							ratio = Math.min(0.9, Math.max(0.1, ratio));
+							first.style.flexGrow = ratio;
+							second.style.flexGrow = 1 - ratio;
+						}
+						function up() {
+							document.removeEventListener('mousemove', move);
+							document.removeEventListener('mouseup', up);
+							if (ratio == null) {return;}
+							tab_handle('ratio', split.closest('.tab').dataset.tab, divider.dataset.path, ratio);
+							termila_resize();
+						}
+						document.addEventListener('mousemove', move);
+						document.addEventListener('mouseup', up);
+					});
+					
+					// mouse reporting for applications that enabled it (data-mouse is the tracking mode), shift keeps local selection
+					let termila_mouse_pane = null; // pane that got the press (gets moves and release too)
+					let termila_mouse_cell = '';
+					let termila_cell_width = 0;
+					window.addEventListener('resize', () => termila_cell_width = 0);
+					function termila_mouse(event, pane, action, button) {
+						if (!termila_cell_width) {
+							const span = document.createElement('span');
+							span.textContent = 'M';
+							span.style.position = 'absolute';
+							span.style.visibility = 'hidden';
+							document.body.appendChild(span);
+							termila_cell_width = span.getBoundingClientRect().width;
+							span.remove();
+						}
+						// line from the bottom (screen is the last lines of the console, hidden ones like folded output or primary screen under the alternate one are skipped but still counted) and column
+						const lines = pane.children;
+						const r = pane.getBoundingClientRect();
+						const y = Math.min(Math.max(event.clientY, r.top), r.bottom - 1);
+						let line = lines.length - 1;
+						while (line > 0 && (lines[line].getClientRects().length == 0 || lines[line].getBoundingClientRect().top > y)) { line--; }
+						const column = Math.max(0, Math.floor((event.clientX - r.left) / termila_cell_width));
+						const cell = (lines.length - 1 - line) + ',' + column;
+						if (action == 2 && cell == termila_mouse_cell) {return;} // moves are reported once per cell
+						termila_mouse_cell = cell;
+						const modifiers = (event.shiftKey ? 1 : 0) | (event.altKey ? 2 : 0) | (event.ctrlKey ? 4 : 0);
+						mouse_handle(pane.dataset.pane, action, button, lines.length - 1 - line, column, modifiers);
+					}
+					document.addEventListener('mousedown', function(event) {
+						const pane = event.target.closest('.console');
+						if (!pane || !(pane.dataset.mouse > 0) || event.shiftKey) {return;}
+						termila_mouse_pane = pane;
+						termila_mouse(event, pane, 1, event.button);
+						event.preventDefault();
+					});
+					document.addEventListener('mouseup', function(event) {
+						const pane = termila_mouse_pane;
+						termila_mouse_pane = null;
+						if (!pane || !(pane.dataset.mouse > 0)) {return;}
+						termila_mouse(event, pane, 3, event.button);
+					});
+					document.addEventListener('mousemove', function(event) {
+						const pane = termila_mouse_pane || event.target.closest('.console');
+						if (!pane || !(pane.dataset.mouse >= 1002) || event.shiftKey) {return;}
+						const button = event.buttons & 1 ? 0 : event.buttons & 4 ? 1 : event.buttons & 2 ? 2 : 3;
+						if (button == 3 && pane.dataset.mouse != 1003) {return;}
+						termila_mouse(event, pane, 2, button);
+					});
+					document.addEventListener('wheel', function(event) {
+						const pane = event.target.closest('.console');
+						if (!pane || !(pane.dataset.mouse > 0) || event.shiftKey || !event.deltaY) {return;}
+						termila_mouse(event, pane, 1, event.deltaY < 0 ? 64 : 65);
+						event.preventDefault();
+					}, {passive: false});
+					document.addEventListener('contextmenu', function(event) {
+						const pane = event.target.closest('.console');
+						if (pane && pane.dataset.mouse > 0 && !event.shiftKey) { event.preventDefault(); }
+					});
+					
+					// clipboard for applications (OSC 52)
+					function termila_clipboard_write(text) {
+						(navigator.clipboard ? navigator.clipboard.writeText(text) : Promise.reject()).catch(() => {
+							// without clipboard api text has to be selected and copied
+							const focused = document.activeElement;
+							const area = document.createElement('textarea');
+							area.value = text;
+							document.body.appendChild(area);
+							area.select();
+							document.execCommand('copy');
+							area.remove();
+							focused.focus();
+						});
+					}
+					function termila_clipboard_read(pane, selection, ask) {
+						const reply = allow => {
+							if (!allow || !navigator.clipboard) { clipboard_handle(pane, selection, ''); return; }
+							navigator.clipboard.readText().then(text => clipboard_handle(pane, selection, text), () => clipboard_handle(pane, selection, ''));
+						};
+						if (!ask) { reply(true); return; }
+						const prompt = document.querySelector('#clipboard_prompt');
+						if (prompt.onanswer) { prompt.onanswer(false); } // only the latest request is asked
+						prompt.onanswer = allow => { prompt.onanswer = null; prompt.classList.remove('visible'); reply(allow); };
+						prompt.classList.add('visible');
+					}
+					
+					function termila_paste_prompt(pane, text) { // warning before pasting multiple lines or control characters without bracketed paste
+						const prompt = document.querySelector('#paste_prompt');
+						const lines = text.split(/\r\n|\r|\n/).length;
+						prompt.querySelector('span').innerText = (lines > 1 ? lines + ' lines' : 'control characters');
+						prompt.querySelector('pre').innerText = text.replace(/[\x00-\x08\x0b\x0c\x0e-\x1f\x7f]/g, c => '^' + String.fromCharCode(c.charCodeAt(0) ^ 0x40));
+						prompt.onanswer = paste => {
+							prompt.onanswer = null;
+							prompt.classList.remove('visible');
+							if (paste) { tab_handle('paste', pane, text, 'confirmed'); }
+						};
+						prompt.classList.add('visible');
+					}
+					
+					// ctrl+click opens links (OSC 8 hyperlinks by id, urls, file:line:col and git hashes are found in the line text)
+					const termila_link_patterns = [
+						['url', /(?:https?|ftp|file):\/\/[^\s"'<>`]*[^\s"'<>`.,;:!?)\]}]/g],
+						['file', /(?:[\w.~-]*\/)+[\w.-]+:\d+(?::\d+)?|[\w-][\w.-]*\.\w+:\d+(?::\d+)?/g],
+						['hash', /\b(?=[0-9]*[a-f])[0-9a-f]{7,40}\b/g],
+					];
+					function termila_link_at(x, y) { // [kind, text] of link under the point or null
+						const range = document.caretRangeFromPoint(x, y);
+						if (!range || range.startContainer.nodeType != Node.TEXT_NODE) {return null;}
+						const line = range.startContainer.parentElement.closest('.console > span');
+						if (!line) {return null;}
+						let offset = range.startOffset; // position within the whole line
+						const walker = document.createTreeWalker(line, NodeFilter.SHOW_TEXT);
+						while (walker.nextNode() && walker.currentNode != range.startContainer) { offset += walker.currentNode.length; }
+						const text = line.textContent.replace(/\u00a0/g, ' ');
+						for (const [kind, pattern] of termila_link_patterns) {
+							for (const match of text.matchAll(pattern)) {
+								if (match.index <= offset && offset <= match.index + match[0].length) { return [kind, match[0]]; }
 							}
-							
-							else if (what.charCode != 0) { term_type(String.fromCharCode(what.charCode)); what.preventDefault(); }
-							
 						}
+						return null;
+					}
+					document.addEventListener('click', function(event) {
+						const pane = event.target.closest('.console');
+						if (!pane || !event.ctrlKey) {return;}
+						const link = event.target.closest('a[data-link]');
+						if (pane.dataset.mouse > 0 && !event.shiftKey) {return;} // application gets the click
+						const found = link ? ['link', link.dataset.link] : termila_link_at(event.clientX, event.clientY);
+						if (found) { tab_handle('open', pane.dataset.pane, ...found); }
+						event.preventDefault();
+					});
+				
+					// keys are encoded in rust (keys.rs) by modes of the focused pane (releases and modifier keys are sent too, for kitty keyboard protocol)
+					function termila_key(event) {
+						if (document.activeElement.tagName != 'BODY'){return;} // allow interaction with other inputs too
+						if (event.isComposing || event.metaKey || ['Dead', 'Unidentified', 'Process', 'AltGraph', 'Meta', 'OS'].includes(event.key)) {return;}
+						if (event.ctrlKey && event.code == 'KeyC' && window.getSelection().toString() != '') {return;} // allow ctrl c copy
+						if (event.ctrlKey && event.code == 'KeyV') {return;} // allow ctrl v paste
+						const altgr = event.getModifierState('AltGraph'); // reported as ctrl+alt on windows
+						const modifiers = (event.shiftKey ? 1 : 0) | (event.altKey && !altgr ? 2 : 0) | (event.ctrlKey && !altgr ? 4 : 0);
+						const kind = event.type == 'keyup' ? 3 : event.repeat ? 2 : 1;
+						key_handle(event.code, event.key, modifiers, kind);
+						event.preventDefault();
+					}
+					document.addEventListener('keydown', termila_key);
+					window.addEventListener('focus', () => { document.body.classList.remove('blurred'); tab_handle('window_focus', '1'); });
+					window.addEventListener('blur', () => { document.body.classList.add('blurred'); tab_handle('window_focus', '0'); });
+					document.addEventListener('keyup', termila_key);
+				
+					function term_type(what) { // text or bytes typed to the focused pane (paste, popups)
 						
-						else if (typeof what == 'string') { // text
+						if (typeof what == 'string') { // text
 							// encode string to bytes and then send it to terminal
 							const encoder = new TextEncoder(); // default is utf-8
 							const bytes = encoder.encode(what);
@@ -231,11 +772,6 @@ impl UI {
 						}
 						
 					}
-					
-
-					document.addEventListener('keydown', function(event) { term_type(event); });
-
-					document.addEventListener('keypress', function(event) { term_type(event); });
 
 					document.addEventListener('paste', function(event) {
 						
@@ -246,9 +782,9 @@ impl UI {
 						event.stopPropagation();
 						event.preventDefault();
 
-						// get data
+						// get data (cleaned, bracketed or confirmed in rust by modes of the focused pane)
 						var clipboardData = event.clipboardData || window.clipboardData;
-						term_type(clipboardData.getData('Text'));
+						tab_handle('paste', '', clipboardData.getData('Text'));
 						
 					});
 					
@@ -257,7 +793,7 @@ impl UI {
 						if (document.activeElement.tagName != 'BODY'){return;}
 						
 						// save selected text
-						document.querySelector('#console').dataset.selection=window.getSelection().toString();
+						document.querySelector('#consoles').dataset.selection=window.getSelection().toString();
 
 						// TODO: save and restore selection on focus out/in
 					});
@@ -357,16 +893,94 @@ impl UI {
         let key_term_handle = webview.call_native( move |args| {
                 if let Some(arg) = args.get(0) {
                     if let Ok(val) = arg.parse::<u8>() {
-						tab_cb1.lock().unwrap().pty.write(val);
+						let mut tabs = tabs_cb1.lock().unwrap();
+						let active = *active_tab_cb1.lock().unwrap();
+						if let Some(tab) = tabs.get_mut(active) {
+							let focused = tab.focused;
+							if let Some(pane) = tab.pane(focused) {
+								pane.pty.write(val);
+							}
+						}
                     }
                 }
             }, None );
         webview.call_js(&format!("var key_term_handle = {};", key_term_handle), Some(false));
+		
+		// key event callback (code, key, modifiers and kind of KeyboardEvent, encoded by modes of the focused pane)
+		let key_handle = webview.call_native( move |args| {
+				let kind = match args.get(3).map(|k| k.as_str()) { Some("2") => keys::KEYS_kind::Repeat, Some("3") => keys::KEYS_kind::Release, _ => keys::KEYS_kind::Press };
+				if let (Some(code), Some(key), Some(modifiers)) = (args.get(0), args.get(1), args.get(2).and_then(|m| m.parse::<u8>().ok())) {
+					let mut tabs = tabs_cb4.lock().unwrap();
+					let active = *active_tab_cb4.lock().unwrap();
+					if let Some(tab) = tabs.get_mut(active) {
+						let focused = tab.focused;
+						if let Some(pane) = tab.pane(focused) {
+							let event = keys::KEYS_event { code, key, shift: modifiers & 1 != 0, alt: modifiers & 2 != 0, ctrl: modifiers & 4 != 0, kind };
+							if let Some(bytes) = keys::encode(&event, &pane.buff.key_modes) {
+								if !pane.pty.exited { pane.pty.write_bytes(&bytes); }
+							}
+						}
+					}
+				}
+			}, None );
+		webview.call_js(&format!("var key_handle = {};", key_handle), Some(false));
+		
+		// mouse event callback (pane, action as 1 press 2 move 3 release, xterm button, line from the bottom of the console, column and modifiers)
+		let mouse_handle = webview.call_native( move |args| {
+				let number = |i: usize| args.get(i).and_then(|a| a.parse::<usize>().ok());
+				if let (Some(id), Some(action), Some(button), Some(line), Some(column), Some(modifiers)) = (number(0), number(1), number(2), number(3), number(4), number(5)) {
+					if let Some(pane) = tabs_cb5.lock().unwrap().iter_mut().find_map(|tab| tab.pane(id)) {
+						let (rows, columns) = (pane.buff.size_rows, pane.buff.size_columns);
+						if line >= rows || columns == 0 { return; } // scrollback isnt reported
+						let action = match action { 2 => keys::KEYS_mouse_action::Move, 3 => keys::KEYS_mouse_action::Release, _ => keys::KEYS_mouse_action::Press };
+						let event = keys::KEYS_mouse { action, button: button as u8, row: rows - 1 - line, column: column.min(columns - 1), shift: modifiers & 1 != 0, alt: modifiers & 2 != 0, ctrl: modifiers & 4 != 0 };
+						if let Some(bytes) = keys::encode_mouse(&event, &pane.buff.key_modes) {
+							if !pane.pty.exited { pane.pty.write_bytes(&bytes); }
+						}
+					}
+				}
+			}, None );
+		webview.call_js(&format!("var mouse_handle = {};", mouse_handle), Some(false));
+		
+		// tab and pane actions callback (tab bar, shortcuts and mouse)
+		let tab_handle = webview.call_native( move |args| {
+				tab_requests_cb.lock().unwrap().push(args);
+			}, None );
+		webview.call_js(&format!("var tab_handle = {};", tab_handle), Some(false));
+		
+		// clipboard content for application that asked for it (or empty text when it was denied)
+		let clipboard_handle = webview.call_native( move |args| {
+				if let (Some(id), Some(selection), Some(text)) = (args.get(0).and_then(|id| id.parse::<usize>().ok()), args.get(1), args.get(2)) {
+					if let Some(pane) = tabs_cb3.lock().unwrap().iter_mut().find_map(|tab| tab.pane(id)) {
+						pane.clipboard_reply(selection, text);
+					}
+				}
+			}, None );
+		webview.call_js(&format!("var clipboard_handle = {};", clipboard_handle), Some(false));
+		
+		
+		// window title
+		webview.call_js(&format!("document.title = {};", UI::escape_js(&options.title)), Some(false));
+		
+		// apply color palette and font
+		webview.call_js(&format!("document.documentElement.style.cssText += `{}`;", options.palette.to_css()), Some(false));
+		
+		// cursor color and blinking (shape is given to each pane)
+		if !options.cursor_color.is_empty() {
+			webview.call_js(&format!("document.documentElement.style.setProperty('--termila_cursor', {});", UI::escape_js(&options.cursor_color)), Some(false));
+		}
+		let blink = if options.cursor_blink == 0 { "none".to_string() } else { format!("termila_cursor_blink {}ms step-end infinite", 2 * options.cursor_blink as u32) };
+		webview.call_js(&format!("document.documentElement.style.setProperty('--termila_cursor_blink', '{}');", blink), Some(false));
+		webview.call_js(&format!("document.body.style.fontFamily = {}; document.body.style.fontSize = '{}px';", UI::escape_js(&options.font), options.font_size), Some(false));
+		
+		// ai settings (used by ai popup)
+		webview.call_js(&format!("var termila_ai = {{url: {}, key: {}, model: {}, prompt: {}}};", UI::escape_js(&options.ai_url), UI::escape_js(&options.ai_key), UI::escape_js(&options.ai_model), UI::escape_js(&options.ai_prompt)), Some(false));
        
 	   
-        // automatically set terminal size
+        // automatically set terminal size (each pane is measured separately)
         webview.call_js(&format!(r#"
-            window.addEventListener('resize', () => {{
+            var size_handle = {};
+            function termila_resize() {{
 
                 const span = document.createElement('span');
                 span.textContent = 'M';
@@ -380,23 +994,21 @@ impl UI {
 
                 document.body.removeChild(span);
 
-                const cols = Math.floor(window.innerWidth / charWidth);
-                const rows = Math.floor(window.innerHeight / charHeight);
-				
-				console.log(cols, rows);
-
-                ({})(cols, rows);
+                document.querySelectorAll('#consoles .console').forEach(c => {{
+                    const cols = Math.floor(c.clientWidth / charWidth);
+                    const rows = Math.floor(c.clientHeight / charHeight);
+                    if (cols > 0 && rows > 0) {{ size_handle(c.dataset.pane, cols, rows); }}
+                }});
 
-            }});"#,
+            }}
+            window.addEventListener('resize', termila_resize);"#,
             webview.call_native( move |args| {
 
-                if let Some(cols) = args.get(0) {
-                    if let Some(rows) = args.get(1) {
-                        if let Ok(c) = cols.parse::<u16>() {
-                            if let Ok(r) = rows.parse::<u16>() {
-                                eprintln!("(info)  UI: resize {}x{}", c, r);
-                                tab_cb2.lock().unwrap().pty.set_size(r,c);
-                            }
+                if let (Some(id), Some(cols), Some(rows)) = (args.get(0), args.get(1), args.get(2)) {
+                    if let (Ok(id), Ok(c), Ok(r)) = (id.parse::<usize>(), cols.parse::<u16>(), rows.parse::<u16>()) {
+                        eprintln!("(info)  UI: resize pane {} {}x{}", id, c, r);
+                        if let Some(pane) = tabs_cb2.lock().unwrap().iter_mut().find_map(|tab| tab.pane(id)) {
+                            pane.pty.set_size(r,c);
                         }
                     }
                 }
@@ -405,7 +1017,31 @@ impl UI {
         ), Some(false));
 		
 		
-		let self_ = Self { webview, next_update, tab, options };
+		// resize window to requested terminal size
+		if let Some((rows, columns)) = options.geometry {
+			webview.call_js(&format!(r#"
+				(function(){{
+					const span = document.createElement('span');
+					span.textContent = 'M';
+					span.style.position = 'absolute';
+					span.style.visibility = 'hidden';
+					document.body.appendChild(span);
+					window.resizeTo({} * span.offsetWidth + window.outerWidth - window.innerWidth, {} * span.offsetHeight + window.outerHeight - window.innerHeight);
+					document.body.removeChild(span);
+				}})();"#, columns, rows), Some(false));
+		}
+		
+		
+		let (wake_sender, wake) = mpsc::sync_channel(1);
+		let window_title = options.title.clone();
+		let mut self_ = Self { webview, next_update, tabs, active_tab, tab_requests, last_id: 0, wake, wake_sender, busy: false, window_title, window_focused: true, options };
+		
+		// first tab
+		self_.open_tab(None);
+		if self_.tabs.lock().unwrap().is_empty() {
+			std::eprintln!("termila: cant start '{}'", self_.options.shell); // always shown (even in release builds)
+			std::process::exit(1);
+		}
 		
 		
 		// popups
@@ -437,20 +1073,19 @@ impl UI {
 			r#"
 			<h3>ASK AI</h3>
 			<input type="text" onchange="
-				const API_KEY = 'YOUR_API_KEY_HERE';
 				(async function () {
 					document.activeElement.blur();
-					const response = await fetch('http://127.0.0.1:8080/v1/chat/completions' /*'https://api.openai.com/v1/chat/completions'*/, {
+					const response = await fetch(termila_ai.url, {
 						method: 'POST',
 						headers: {
-							Authorization: 'Bearer '+API_KEY,
+							Authorization: 'Bearer '+termila_ai.key,
 							'Content-Type': 'application/json',
 						},
 						body: JSON.stringify({
-							model: 'gpt-4o-mini',
+							model: termila_ai.model,
 							messages: [
-								{ role: 'system', content: 'You are a helpful assistant. \\nYou help the user with terminal interaction by explaining commands, giving solutions to errors and evaluating safety of commands. \\nAnswer shortly, dont use markdown.' },
-								{ role: 'user', content: document.querySelector('#console').dataset.selection+'\\n\\n'+document.querySelector('div#ai input').value }
+								{ role: 'system', content: termila_ai.prompt },
+								{ role: 'user', content: document.querySelector('#consoles').dataset.selection+'\\n\\n'+document.querySelector('div#ai input').value }
 							],
 							max_tokens: 50
 						}),
@@ -546,16 +1181,35 @@ impl UI {
 	// TODO: custom popup_* -> plugin interface = just shared object with one function `void termila_custom_popup_init(void* webview, function add_popup);`
 	
 	
+	fn escape_js (text: &str) -> String { // quoted javascript string literal
+		let mut result = String::with_capacity(text.len()+2);
+		result.push('\'');
+		for c in text.chars() {
+			match c {
+				'\\' => result.push_str("\\\\"),
+				'\'' => result.push_str("\\'"),
+				'\n' => result.push_str("\\n"),
+				'\r' => result.push_str("\\r"),
+				c if (c as u32) < 0x20 || c == '\u{2028}' || c == '\u{2029}' => result.push_str(&format!("\\u{:04x}", c as u32)),
+				_ => result.push(c),
+			}
+		}
+		result.push('\'');
+		return result;
+	}
+	
 	fn escape_text (text: &String) -> String {
 		let mut result = String::with_capacity(text.len());
 		for c in text.chars() {
 			match c {
 				' '  => result.push_str("&nbsp;"),
+				'&'  => result.push_str("&amp;"),
 				'\\' => result.push_str("\\\\"),
 				'<'  => result.push_str("&lt;"),
 				'>'  => result.push_str("&gt;"),
 				'\n' => result.push_str("<br>"),
 				'`'  => result.push_str("\\`"),
+				'$'  => result.push_str("\\$"),
 				_    => result.push(c),
 			}
 		}
@@ -646,1128 +1300,761 @@ impl UI {
 	} */
 	
 	
-	fn handle (&mut self) {
-		
-		self.tab.lock().unwrap().process(& self.webview, true);
-		
-		if Instant::now() >= self.next_update {
-			
-			// store debug data
-			#[cfg(debug_assertions)]
-			let delay = self.next_update.elapsed();
-			#[cfg(debug_assertions)]
-			let start = Instant::now();
-			
-			// plan next update
-			self.next_update = Instant::now() + Duration::from_millis(3);
-			
-			// update ui
-			WebView::handle_once();
-			
-			// print debug data
-			#[cfg(debug_assertions)]
-			if delay > Duration::from_millis(3) {
-				eprintln!("(warning)  UI: update delay {:?}", delay);
-			}
-			#[cfg(debug_assertions)]
-			if start.elapsed() > Duration::from_millis(3) {
-				eprintln!("(warning)  UI: update duration {:?}", start.elapsed());
+	fn open_tab (&mut self, cwd: Option<String>) { // shell starts in cwd (or in working_directory from options)
+		
+		self.last_id += 1;
+		let id = self.last_id;
+		self.last_id += 1;
+		let pane_id = self.last_id;
+		let tab = match TAB::new(&self.options, id, pane_id, cwd, &self.wake_sender) {
+			Some(tab) => tab,
+			None => {
+				eprintln!("(error)  UI: cant open new tab");
+				return;
 			}
-        }
+		};
 		
+		// tab label is filled by show_titles
+		self.webview.call_js(&format!(
+			r#"
+			document.querySelector('#consoles').insertAdjacentHTML('beforeend', '<div class="tab" id="view-{}" data-tab="{}"></div>');
+			document.querySelector('#tabbar').insertAdjacentHTML('beforeend', `<button id="tab-{}" onmousedown="event.preventDefault()" onclick="tab_handle('select', '{}')"><span class="label"></span><span class="close" onclick="event.stopPropagation(); tab_handle('close', '{}')">&#x2715;</span></button>`);
+			"#,
+			id, id, id, id, id
+		), Some(false));
+		self.show_panes(&tab);
+		
+		// new tab is placed after the active one and becomes active
+		let mut tabs = self.tabs.lock().unwrap();
+		let mut active = self.active_tab.lock().unwrap();
+		let index = if tabs.is_empty() { 0 } else { *active + 1 };
+		tabs.insert(index, tab);
+		*active = index;
+		drop(active);
+		drop(tabs);
+		
+		self.show_tabs();
 	}
 	
-}
-
-
-struct TAB<'a> {
-	buff: BUFF<'a>,
-	pty: PTY,
-	fps: u8,
-	next_update: std::time::Instant,
-}
-impl TAB<'_> {
-	
-	fn new(options: &OPTIONS) -> Option<Self> {
+	fn close_tab (&mut self, id: usize) {
 		
-		// init parsser
-		let mut buff = BUFF::new();
+		let mut tabs = self.tabs.lock().unwrap();
+		let mut active = self.active_tab.lock().unwrap();
+		let Some(index) = tabs.iter().position(|tab| tab.id == id) else { return; };
 		
-		// setup terminal
-		let mut pty = match PTY::new(options.shell.clone(), options.shell_args.clone(), options.term.clone()) {
-			Some(pty) => pty,
-			None => {
-				return None;
-			}
-		};
-		pty.set_size(30,100); // TODO: set correct size on startup
+		// dropping the tab closes its ptys (and the shells get hangup)
+		let tab = tabs.remove(index);
+		self.webview.call_js(&format!("document.getElementById('view-{}').remove(); document.getElementById('tab-{}').remove();", id, id), Some(false));
+		drop(tab);
 		
-		// set fps
-		let fps: u8 = 5;
+		// close the window with the last tab
+		if tabs.is_empty() {
+			std::process::exit(0);
+		}
 		
-		// set initial next update
-		let next_update = Instant::now();
+		if index < *active || *active >= tabs.len() {
+			*active -= 1;
+		}
+		drop(active);
+		drop(tabs);
 		
-		Some(Self{buff, pty, fps, next_update })
+		self.show_tabs();
 	}
 	
-	fn process (&mut self, webview: &HUI::WebView/*ui: & UI*/, update_ui: bool) {
+	fn close_pane (&mut self, tab_id: usize, pane_id: usize) { // closing the last pane closes the tab
 		
-		if Instant::now() >= self.next_update {
-			
-			// store debug data
-			#[cfg(debug_assertions)]
-			let delay = self.next_update.elapsed();
-			#[cfg(debug_assertions)]
-			let start = Instant::now();
-			
-			// plan next update
-			self.next_update = Instant::now() + Duration::from_millis((1000u64 / self.fps as u64));
-			
-			// do work
-			let mut counter = 0;
-			while true {
-				// performance note (release build): 'buff_write' has no performance impact when idle but under stress takes much more time than 'read_char' (which seems to have constant impact not depending on load) and sometimes 'ui_update' (which is most time consuming task, but can be tuned with fps setting) 
-				
-				// get data
-				let chr = self.read_char();
-			
-				// update size first
-				let (trows,tcolumns) = self.pty.get_size();
-				(self.buff.size_rows, self.buff.size_columns) = (trows as usize, tcolumns as usize);
-			
-				// process data
-				self.buff.write_raw(chr);
-				counter+=1;
-				
-				// stop when done or exceeded limit
-				if chr == '\0' {break;}
-				if counter >= 10240 { eprintln!("(warning)  TAB: processing read counter exceeded"); break; }
-			}
-			
-			// update UI
-			if update_ui {
-				//self.buff.update_full(& webview);
-				self.buff.update_partial(& webview);
-			}
-			
-			// print debug data
-			#[cfg(debug_assertions)]
-			if delay > Duration::from_millis((1000u64 / self.fps as u64)) {
-				eprintln!("(warning)  TAB: update delay {:?}", delay);
+		let mut tabs = self.tabs.lock().unwrap();
+		let Some(tab) = tabs.iter_mut().find(|tab| tab.id == tab_id) else { return; };
+		
+		if tab.panes.len() <= 1 {
+			drop(tabs);
+			self.close_tab(tab_id);
+			return;
+		}
+		
+		tab.close(pane_id);
+		self.show_panes(tab);
+		drop(tabs);
+		
+		self.show_tabs();
+	}
+	
+	fn clipboard (&self, pane: &mut PANE, request: BUFF_clipboard) { // OSC 52 from application
+		match request {
+			BUFF_clipboard::Set(text) => {
+				self.webview.call_js(&format!("termila_clipboard_write({});", UI::escape_js(&text)), Some(false));
 			}
-			#[cfg(debug_assertions)]
-			if start.elapsed() > Duration::from_millis((1000u64 / self.fps as u64)) {
-				eprintln!("(warning)  TAB: update duration {:?}", start.elapsed());
+			BUFF_clipboard::Query(selection) => {
+				match self.options.clipboard_read.as_str() {
+					"deny" => pane.clipboard_reply(&selection, ""),
+					read => { self.webview.call_js(&format!("termila_clipboard_read('{}', {}, {});", pane.id, UI::escape_js(&selection), read == "prompt"), Some(false)); }
+				}
 			}
-			//eprintln!("(benchmark)  TAB: time_read {:?}, time_buff {:?}, time_js {:?}", self.time_read, self.time_buff, self.time_js);
-			
-        }
-		
+		}
 	}
 	
-	fn read_char(&mut self) -> char {
-
-		// read the first byte
-		let mut buf = Vec::new();
-		buf.push(self.pty.read());
-
-		// determine how many bytes we need
-		let needed = match buf[0] {
-			0x00..=0x7F => 1,  // ASCII
-			0xC0..=0xDF => 2,  // 2-byte sequence
-			0xE0..=0xEF => 3,  // 3-byte sequence
-			0xF0..=0xF7 => 4,  // 4-byte sequence
-			_ => 0,            // invalid leading byte
+	fn open_link (&self, pane_id: usize, kind: &str, target: &str) { // runs link_handler (or system default opener) in the directory of the pane shell
+		
+		let tabs = self.tabs.lock().unwrap();
+		let Some(pane) = tabs.iter().flat_map(|tab| tab.panes.iter()).find(|pane| pane.id == pane_id) else { return; };
+		
+		// OSC 8 links come by id (so the page never passes uri itself)
+		let (kind, target) = match kind {
+			"link" => match target.parse::<usize>().ok().and_then(|id| pane.buff.links.get(id.wrapping_sub(1))) {
+				Some(uri) => ("url", uri.clone()),
+				None => return,
+			},
+			"url" | "file" | "hash" => (kind, target.to_string()),
+			_ => return,
 		};
-
-		// read more bytes if needed
-		while buf.len() < needed {
-			buf.push(self.pty.read());
-		}
-
-		// try to decode
-		match str::from_utf8(&buf) {
-			Ok(s) => s.chars().next().unwrap_or(' '),
-			Err(_) => ' ',
-		}
+		let cwd = pane.cwd();
+		drop(tabs);
 		
+		let command: Vec<String> = if self.options.link_handler.is_empty() {
+			
+			// system opener knows only urls and files (without position), other schemes (ssh:, custom handlers, ...) have to be allowed by link_handler
+			let target = match kind {
+				"url" => {
+					let scheme = target.split_once(':').map_or("", |(scheme, _)| scheme).to_ascii_lowercase();
+					if !matches!(scheme.as_str(), "http" | "https" | "mailto" | "file") {
+						eprintln!("(warning)  UI: link '{}' not opened, scheme '{}' needs link_handler", target, scheme);
+						return;
+					}
+					target
+				}
+				"file" => {
+					let mut path = target.as_str();
+					for _ in 0..2 {
+						if let Some((p, n)) = path.rsplit_once(':') {
+							if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) { path = p; }
+						}
+					}
+					path.to_string()
+				}
+				_ => {
+					eprintln!("(info)  UI: no default handler for {} '{}'", kind, target);
+					return;
+				}
+			};
+			
+			// files are only opened, never run
+			let path = if kind == "file" { Some(target.clone()) }
+				else if target.to_ascii_lowercase().starts_with("file://") { Some(buff::percent_decode(&target[7..]).split_once('/').map_or(String::new(), |(_, path)| format!("/{}", path))) } // without host
+				else { None };
+			if let Some(path) = path {
+				if UI::executable(cwd.as_deref(), &path) {
+					eprintln!("(warning)  UI: link '{}' not opened, it is executable", target);
+					return;
+				}
+			}
+			
+			#[cfg(target_os = "linux")]
+			{ vec!["xdg-open".to_string(), target] }
+			#[cfg(target_os = "windows")]
+			{
+				// explorer opens it with the default application (no cmd.exe, which would parse the link again), but links that look like commands are refused anyway
+				if target.contains(['&', '|', '<', '>', '^', '"', '%', '!', '\n', '\r']) {
+					eprintln!("(warning)  UI: link '{}' not opened, it contains shell characters", target);
+					return;
+				}
+				vec!["explorer.exe".to_string(), target]
+			}
+		}
+		else {
+			let mut args: Vec<String> = self.options.link_handler.split_whitespace().map(|arg| arg.replace("{kind}", kind).replace("{}", &target)).collect();
+			if !self.options.link_handler.contains("{}") { args.push(target); }
+			args
+		};
+		
+		eprintln!("(info)  UI: opening link {:?}", command);
+		let mut process = std::process::Command::new(&command[0]);
+		process.args(&command[1..]);
+		if let Some(cwd) = cwd { process.current_dir(cwd); }
+		match process.spawn() {
+			Ok(mut child) => { thread::spawn(move || { let _ = child.wait(); }); } // collected in background
+			Err(e) => { std::eprintln!("termila: cant run link handler '{}': {}", command[0], e); }
+		}
 	}
-
-}
-
-
-struct BUFF_formated_text<'l> {
-    text: String, // console text
-    style: HashMap<&'l str, &'l str>, // css attributes
-    updated: bool, // changed but not displayed
-    id: usize, // html id, 0 means unset, set when update runs, '#t-<value>'
-}
-struct BUFF<'a> {
-    formated_text: Vec<BUFF_formated_text<'a>>, // html_vec [ [<html>,<css or classn>,<q updated>], ] // TODO: ensure that blanks are not preserved
-    formated_text_last_id: usize,
-    formated_text_changes: usize,
-	
-    current_escape: String, // multi-character special commands; contains the sequence from the escape byte to the last character read; if we are not currently reading any sequence (after previous was finished) it is empty string
-    current_escape_max_length: usize, // this is to avoid breaking terminal with unsupported/malicious sequences; the value depends on sequence type
-    
-	cursor_position_index: usize,
-    cursor_position_character: usize,
-	
-	size_rows: usize,
-    size_columns: usize,
-}
-impl BUFF<'_> {
 	
-    fn new() -> Self {
-        unsafe {
-            Self {
-                formated_text: vec![/*BUFF_formated_text{text:"".to_string(),style:[].iter().cloned().collect(),updated:false,id:0},*/],
-                formated_text_last_id: 0,
-				formated_text_changes: 0,
-				current_escape: "".to_string(),
-                current_escape_max_length: 0,
-                cursor_position_index: 0,
-                cursor_position_character: 0,
-				size_rows: usize::MAX,
-				size_columns: usize::MAX,
-            }
-        }
-    }
-
-    
-	fn write_buff(&mut self, chr: char) {
-
-		// fix invalid cursor position
-        if self.cursor_position_index >= self.formated_text.len(){
-            eprintln!("(warning)  BUFF: invalid position in buffer - reseting");
-            self.cursor_position_index = self.formated_text.len();
-            self.cursor_position_character = 0;
-            self.formated_text.push( BUFF_formated_text{text:"".to_string(),style:[].iter().cloned().collect(),updated:true,id:0} );
-			self.formated_text_changes += 1;
-        }
-        if self.cursor_position_character > self.formated_text.get(self.cursor_position_index).unwrap().text.len() {
-            eprintln!("(warning)  BUFF: invalid position in buffer - reseting");
-            self.cursor_position_character = self.formated_text.get(self.cursor_position_index).unwrap().text.len();
-        }
-		
-		
-        // remove character at cursor position if overwriting and if its not newline
-        let mut index = self.cursor_position_index;
-        let mut character = self.cursor_position_character;
-		if self.formated_text.get(index).unwrap().text.get(character..character+1).unwrap_or("\0") == "\0" { // move to at character position if we are not already
-			self.iter_next(& mut index,& mut character);
+	fn executable (cwd: Option<&str>, path: &str) -> bool { // file the system opener would run instead of opening it
+		#[cfg(target_os = "windows")]
+		let path = path.strip_prefix('/').filter(|p| p.get(1..2) == Some(":")).unwrap_or(path); // '/C:/...' from file uri
+		let path = match cwd { Some(cwd) => std::path::Path::new(cwd).join(path), None => std::path::PathBuf::from(path) };
+		let extension = path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+		#[cfg(target_os = "linux")]
+		{
+			use std::os::unix::fs::PermissionsExt;
+			return extension == "desktop" || std::fs::metadata(&path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0);
 		}
-		
-        if self.formated_text.get(index).unwrap().text.get(character..character+1).unwrap_or("\0") != "\n" {
-
-            let mut start = character/*+1*/;
-            let mut end = character+1/*+1*/;
-
-            while ! self.formated_text.get(index).unwrap().text.is_char_boundary(start) && start > 0 {
-                start-=1;
-            }
-            while ! self.formated_text.get(index).unwrap().text.is_char_boundary(end) && end <= self.formated_text.get(index).unwrap().text.len() {
-                end+=1;
-            }
-            if end > self.formated_text.get(index).unwrap().text.len() { end = self.formated_text.get(index).unwrap().text.len(); }
-
-            self.formated_text.get_mut(index).unwrap().text.replace_range(start..end, "");
-			
-			if !self.formated_text.get(index).unwrap().updated {
-				self.formated_text.get_mut(index).unwrap().updated = true;
-				self.formated_text_changes += 1;
+		#[cfg(target_os = "windows")]
+		return matches!(extension.as_str(), "exe" | "com" | "bat" | "cmd" | "lnk" | "url" | "ps1" | "vbs" | "vbe" | "js" | "jse" | "wsf" | "wsh" | "msi" | "msc" | "scr" | "pif" | "hta" | "cpl" | "reg" | "jar" | "appref-ms");
+	}
+	
+	fn show_titles (&mut self) { // tab labels and window title follow the focused pane (title set by the application, foreground process or shell name)
+		
+		let shell = self.options.shell.rsplit(['/', '\\']).next().unwrap_or("").to_string();
+		let mut js = String::new();
+		let mut window_title = self.options.title.clone();
+		
+		let mut tabs = self.tabs.lock().unwrap();
+		let active = *self.active_tab.lock().unwrap();
+		for (i, tab) in tabs.iter_mut().enumerate() {
+			let title = tab.title();
+			if i == active {
+				if let Some(title) = &title { window_title = title.clone(); }
 			}
-
-        }
+			let label = title.unwrap_or(shell.clone());
+			if label != tab.label {
+				js.push_str(&format!("document.querySelector('#tab-{} > .label').textContent = {};", tab.id, UI::escape_js(&label)));
+				tab.label = label;
+			}
+		}
+		drop(tabs);
 		
-
-        // place character
-        let mut pos = self.cursor_position_character;
-        while ! self.formated_text.get(self.cursor_position_index).unwrap().text.is_char_boundary(pos) && pos > 0 {
-            pos-=1;
-        }
-        self.formated_text.get_mut(self.cursor_position_index).unwrap().text.insert(pos, chr);
-		if !self.formated_text.get(self.cursor_position_index).unwrap().updated {
-			self.formated_text.get_mut(self.cursor_position_index).unwrap().updated = true;
-			self.formated_text_changes += 1;
+		if window_title != self.window_title {
+			js.push_str(&format!("document.title = {};", UI::escape_js(&window_title)));
+			self.window_title = window_title;
+		}
+		if !js.is_empty() {
+			self.webview.call_js(&js, Some(false));
 		}
-        self.cursor_position_character += chr.len_utf8();
+	}
+	
+	fn show_panes (&self, tab: &TAB) { // rebuild pane layout of the tab (existing consoles are moved into the new layout)
+		self.webview.call_js(&format!(
+			r#"
+			(function(){{
+				const view = document.getElementById('view-{}');
+				const t = document.createElement('div');
+				t.innerHTML = `{}`;
+				t.querySelectorAll('.slot').forEach(slot => {{
+					let c = document.getElementById('console-' + slot.dataset.pane);
+					if (!c) {{
+						c = document.createElement('p');
+						c.className = 'console';
+						c.id = 'console-' + slot.dataset.pane;
+						c.dataset.pane = slot.dataset.pane;
+					}}
+					c.style.flexGrow = slot.style.flexGrow;
+					slot.replaceWith(c);
+				}});
+				view.replaceChildren(...t.childNodes);
+				if (document.querySelector('#menu button#autoscroll').dataset.checked!='true') {{ view.querySelectorAll('.console').forEach(c => c.scrollTop = c.scrollHeight); }}
+			}})();
+			"#,
+			tab.id, tab.layout.html("", 1.0)
+		), Some(false));
+	}
+	
+	fn show_tabs (&self) { // sync tab bar order, active tab, focused panes and sizes to DOM
 		
-    }
-
-    fn write_raw(&mut self, mut chr: char) {
-
-		if chr == '\x00' {return;} // never accept '\0' for processing - pty implementation returns it when there are no new bytes (it isnt shown anyway and even escape sequences wont contain it)
-
-
-        if self.current_escape.len() == 0 { // regular text
-			
-            if chr == '\x00' { // null
-                // just dont display it
-            }
-            else if chr == '\x07' { // bell
-                eprintln!("(info)  BUFF: bell !!!");
-                // TODO: audio
-            }
-            else if chr == '\x08' { // backspace
-			
-				// the cursour should move one character to the left, but its not supposed to delete it
+		let tabs = self.tabs.lock().unwrap();
+		let active = *self.active_tab.lock().unwrap();
+		
+		let order: Vec<String> = tabs.iter().map(|tab| format!("'tab-{}'", tab.id)).collect();
+		let focused: Vec<String> = tabs.iter().map(|tab| format!("'console-{}'", tab.focused)).collect();
+		self.webview.call_js(&format!(
+			r#"
+			(function(){{
+				const bar = document.querySelector('#tabbar');
+				[{}].forEach(id => bar.appendChild(document.getElementById(id)));
+				document.querySelectorAll('#consoles > .tab, #tabbar > button').forEach(e => e.classList.toggle('active', e.id == 'view-{}' || e.id == 'tab-{}'));
+				document.querySelectorAll('#consoles .console').forEach(e => e.classList.toggle('focused', [{}].includes(e.id)));
+				document.querySelector('#tabbar > button.active').scrollIntoView();
+				termila_resize();
+			}})();
+			"#,
+			order.join(","), tabs[active].id, tabs[active].id, focused.join(",")
+		), Some(false));
+	}
+	
+	fn tab_action (&mut self, action: &[String]) {
+		
+		let count = self.tabs.lock().unwrap().len();
+		let active = *self.active_tab.lock().unwrap();
+		let active_id = self.tabs.lock().unwrap()[active].id;
+		let id = action.get(1).and_then(|id| id.parse::<usize>().ok());
+		
+		match action.get(0).map(|a| a.as_str()) {
 			
-				let mut c = self.get_cursor_c();
-				if c > 1 { c-=1; }
-				self.set_cursor_cr(c,self.get_cursor_r());
-
-                /*if self.cursor_position_character > 0 { // delete previous character
-                    self.formated_text.get_mut(self.cursor_position_index).unwrap().text.replace_range(self.cursor_position_character-1..self.cursor_position_character, "");
-                    self.cursor_position_character -= 1;
-                    self.formated_text.get_mut(self.cursor_position_index).unwrap().updated = true;
-                }
-                else if self.cursor_position_index > 0 { // find and then delete previous character
-                    if let Some(ff) = self.formated_text.iter_mut().skip(self.cursor_position_index).rev().find(|f| f.text.len()>=1) {
-                        ff.text.pop();
-                        ff.updated = true;
-                    }
-                    else {
-                        eprintln!("CANT BACKSPACE, NO PREVIOUS CHARACTER");
-                    }
-                }*/
-
-            }
-            else if chr == '\n' || chr == '\x0b' || chr == '\x0c' { // newline \n \v \f
+			// tabs
+			Some("new") => { // in the directory of the focused pane
+				let cwd = self.tabs.lock().unwrap()[active].focused_cwd();
+				self.open_tab(cwd);
+			}
+			Some("close") => self.close_tab(id.unwrap_or(active_id)),
+			Some("select") => {
+				let index = self.tabs.lock().unwrap().iter().position(|tab| Some(tab.id) == id);
+				if let Some(index) = index {
+					*self.active_tab.lock().unwrap() = index;
+					self.show_tabs();
+				}
+			}
+			Some("next") => {
+				*self.active_tab.lock().unwrap() = (active + 1) % count;
+				self.show_tabs();
+			}
+			Some("previous") => {
+				*self.active_tab.lock().unwrap() = (active + count - 1) % count;
+				self.show_tabs();
+			}
+			Some("move") => { // move active tab by offset
+				let offset = action.get(1).and_then(|o| o.parse::<isize>().ok()).unwrap_or(0);
+				let index = active as isize + offset;
+				if index >= 0 && (index as usize) < count {
+					self.tabs.lock().unwrap().swap(active, index as usize);
+					*self.active_tab.lock().unwrap() = index as usize;
+					self.show_tabs();
+				}
+			}
 			
-				let mut r = self.get_cursor_r();
-				if r == 0 {
-					self.set_cursor_cr(99999,0);
-					self.write_buff('\n');
+			// panes (of the active tab)
+			Some("split") => {
+				self.last_id += 1;
+				let cwd = self.tabs.lock().unwrap()[active].focused_cwd();
+				let Some(pane) = PANE::new(&self.options, self.last_id, cwd, &self.wake_sender) else {
+					eprintln!("(error)  UI: cant open new pane");
+					return;
+				};
+				let mut tabs = self.tabs.lock().unwrap();
+				tabs[active].split(action.get(1).map(|a| a.as_str()) == Some("vertical"), pane);
+				self.show_panes(&tabs[active]);
+				drop(tabs);
+				self.show_tabs();
+			}
+			Some("close_pane") => {
+				let focused = self.tabs.lock().unwrap()[active].focused;
+				self.close_pane(active_id, focused);
+			}
+			Some("select_pane") => {
+				let mut tabs = self.tabs.lock().unwrap();
+				if let Some(id) = id.filter(|id| tabs[active].panes.iter().any(|pane| pane.id == *id)) {
+					tabs[active].focused = id;
 				}
-				else {
-					self.set_cursor_cr(1,r-1);
+				drop(tabs);
+				self.show_tabs();
+			}
+			Some("focus") => { // focus neighbouring pane in direction
+				self.tabs.lock().unwrap()[active].focus(action.get(1).map_or("", |a| a.as_str()));
+				self.show_tabs();
+			}
+			Some("resize") => { // move border of focused pane in direction
+				let mut tabs = self.tabs.lock().unwrap();
+				tabs[active].resize(action.get(1).map_or("", |a| a.as_str()));
+				self.show_panes(&tabs[active]);
+				drop(tabs);
+				self.show_tabs();
+			}
+			Some("ratio") => { // divider was dragged (DOM is already updated)
+				let mut tabs = self.tabs.lock().unwrap();
+				if let (Some(tab), Some(path), Some(ratio)) = (tabs.iter_mut().find(|tab| Some(tab.id) == id), action.get(2), action.get(3).and_then(|r| r.parse::<f32>().ok())) {
+					tab.layout.set_ratio(path, ratio);
 				}
-				
-            }
-            else if chr == '\r' { // carriage return
-				self.set_cursor_cr(1,self.get_cursor_r());
-            }
-            else if chr == '\x1b' { // escape chracter
-                self.current_escape.push('\x1b'); // start escape sequence
-            }
-            else { // any other character
-                self.write_buff(chr);
-            }
-
-        }
-
-
-        else { // escape sequence
+			}
 			
-            self.current_escape.push(chr);
-            self.current_escape_max_length = 3;
+			// paste (to the focused pane, or to the pane the warning was shown for once confirmed)
+			Some("paste") => {
+				let mut tabs = self.tabs.lock().unwrap();
+				let pane_id = id.unwrap_or(tabs[active].focused);
+				let text = action.get(2).map_or("", |t| t.as_str());
+				let confirmed = action.get(3).map(|c| c.as_str()) == Some("confirmed");
+				if let Some(pane) = tabs.iter_mut().find_map(|tab| tab.pane(pane_id)) {
+					if !confirmed && self.options.paste_warning && !pane.buff.key_modes.bracketed_paste && keys::paste_unsafe(text) {
+						self.webview.call_js(&format!("termila_paste_prompt('{}', {});", pane.id, UI::escape_js(text)), Some(false));
+					}
+					else if !pane.pty.exited {
+						pane.pty.write_bytes(&keys::paste(text, &pane.buff.key_modes));
+					}
+				}
+			}
 			
+			// window focus (for focus events of the focused pane)
+			Some("window_focus") => self.window_focused = action.get(1).map(|f| f.as_str()) == Some("1"),
 			
+			// links
+			Some("open") => { // ctrl+click (pane, kind, link text or OSC 8 link id)
+				if let (Some(pane), Some(kind), Some(target)) = (id, action.get(2), action.get(3)) {
+					self.open_link(pane, kind, target);
+				}
+			}
 			
-			// list of all common sequences here: https://xtermjs.org/docs/api/vtfeatures/
+			_ => { eprintln!("(warning)  UI: unknown tab action {:?}", action); }
+		}
+	}
+	
+	
+	fn handle (&mut self) {
+		
+		// tab actions requested from js
+		let requests: Vec<Vec<String>> = self.tab_requests.lock().unwrap().drain(..).collect();
+		for request in requests {
+			self.tab_action(&request);
+		}
+		
+		// every pane keeps reading its shell output, only the visible ones update DOM (hidden ones catch up once shown)
+		let exited: Vec<(usize, usize)> = {
+			let mut tabs = self.tabs.lock().unwrap();
+			let active = *self.active_tab.lock().unwrap();
+			let mut exited = vec![];
+			self.busy = false;
+			for (i, tab) in tabs.iter_mut().enumerate() {
+				let focused = tab.focused;
+				for pane in tab.panes.iter_mut() {
+					pane.report_focus(self.window_focused && i == active && pane.id == focused);
+					self.busy |= pane.process(& self.webview, i == active);
+					for request in std::mem::take(&mut pane.buff.clipboard) {
+						self.clipboard(pane, request);
+					}
+					if pane.pty.exited { exited.push((tab.id, pane.id)); }
+				}
+			}
+			exited
+		};
+		self.show_titles();
+		
+		// close panes whose shell exited (unless asked to keep them)
+		if !self.options.hold {
+			for (tab_id, pane_id) in exited {
+				self.close_pane(tab_id, pane_id);
+			}
+		}
+		
+		if Instant::now() >= self.next_update {
 			
+			// store debug data
+			#[cfg(debug_assertions)]
+			let delay = self.next_update.elapsed();
+			#[cfg(debug_assertions)]
+			let start = Instant::now();
 			
-			// OSC sequences
-			if self.current_escape.starts_with("\x1b]") || self.current_escape.starts_with("\u{9D}") { 
-
-                self.current_escape_max_length = 1024;
-
-				// ending sequence
-                if self.current_escape.ends_with("\x07") || self.current_escape.ends_with("\x1b\\") { 
-				
-					// simplify parsing by removing starting and ending
-					let final_escape = &self.current_escape[
-						if self.current_escape.starts_with("\x1b]") { 2 } 
-						else if self.current_escape.starts_with("\u{9D}") { 2 } // its encoded as two bytes by utf8
-						else { 0 }
-						..
-						self.current_escape.len() 
-						- 
-						if self.current_escape.ends_with("\x07") { 1 } 
-						else if self.current_escape.ends_with("\x1b\\") { 2 } 
-						else { 0 }
-					];
-				
-				
-					if final_escape.starts_with("0;") { // set title
-						// TODO: member called title
-						// = &final_escape[2..self.current_escape.len()]
-					}
-					
-					// else if ... // TODO: many more
-
-
-                    // end sequence
-                    self.current_escape = "".to_string();
-                }
-
-            }
-
-
-			// CSI sequences
-			if self.current_escape.starts_with("\x1b[") || self.current_escape.starts_with("\u{9B}") {
-				
-				self.current_escape_max_length = 16;
-				
-				// ending sequence
-				if (0x40..=0x7E).contains(self.current_escape.as_bytes().last().unwrap()) && self.current_escape.len() > 2 {
-					
-					// remove starting bytes (always two)
-					let final_escape = &self.current_escape[2..];
-					
-					
-					if final_escape.ends_with("m") { // simple color code
-						
-						// convert escape to css
-						fn escape_to_css(sequence: String) -> HashMap<&'static str, &'static str> {
-
-							// strip off the "\x1b[" or "\u{9B}" prefix and the trailing 'm'
-							let inner = &sequence[2..sequence.len() - 1];
-							
-							// init css store
-							let mut css: HashMap<&'static str, &'static str> = [].iter().cloned().collect();
-
-							// function to convert 0–255 xterm color code to rgb
-							fn xterm256_to_rgb(idx: u8) -> (u8, u8, u8) {
-								
-								// 0–15: basic ANSI colors
-								const BASIC: &[(u8,u8,u8)] = &[
-									(0,0,0),       (128,0,0),   (0,128,0),   (128,128,0),
-									(0,0,128),     (128,0,128), (0,128,128), (192,192,192),
-									(128,128,128), (255,0,0),   (0,255,0),   (255,255,0),
-									(0,0,255),     (255,0,255), (0,255,255), (255,255,255),
-								];
-								if idx < 16 {
-									return BASIC[idx as usize];
-								}
-								
-								// 16–231: 6×6×6 color cube
-								if idx < 232 {
-									let ci = idx - 16;
-									let r = ci / 36;
-									let g = (ci % 36) / 6;
-									let b = ci % 6;
-									let level = |n| if n == 0 { 0 } else { 55 + n * 40 };
-									return (level(r), level(g), level(b));
-								}
-								
-								// 232–255: grayscale ramp
-								let gray = 8 + (idx - 232) * 10;
-								return (gray, gray, gray);
-								
-							}
-
-							// split by ';' to handle multiple codes, no parameters is equivalent to '0'
-							let parts: Vec<&str> = if inner.is_empty() {
-									vec!["0"]
-								} else {
-									inner.split(';').collect()
-								};
-
-							let mut iter = parts.iter().peekable();
-
-							while let Some(&code) = iter.next() {
-								match code {
-									
-									// Reset
-									"0" => {
-										css.insert("color","unset");
-										css.insert("background-color","unset");
-									}
-									
-									// Foreground standard
-									"30" => {css.insert("color","black");}
-									"31" => {css.insert("color","red");}
-									"32" => {css.insert("color","green");}
-									"33" => {css.insert("color","yellow");}
-									"34" => {css.insert("color","blue");}
-									"35" => {css.insert("color","magenta");}
-									"36" => {css.insert("color","cyan");}
-									"37" => {css.insert("color","white");}
-
-									// Background standard
-									"40" => {css.insert("background-color","black");}
-									"41" => {css.insert("background-color","red");}
-									"42" => {css.insert("background-color","green");}
-									"43" => {css.insert("background-color","yellow");}
-									"44" => {css.insert("background-color","blue");}
-									"45" => {css.insert("background-color","magenta");}
-									"46" => {css.insert("background-color","cyan");}
-									"47" => {css.insert("background-color","white");}
-
-									// Foreground bright
-									"90" => {css.insert("color","gray");}
-									"91" => {css.insert("color","lightcoral");}
-									"92" => {css.insert("color","lightgreen");}
-									"93" => {css.insert("color","lightyellow");}
-									"94" => {css.insert("color","lightskyblue");}
-									"95" => {css.insert("color","violet");}
-									"96" => {css.insert("color","lightcyan");}
-									"97" => {css.insert("color","white");}
-
-									// Background bright
-									"100" => {css.insert("background-color","gray");}
-									"101" => {css.insert("background-color","lightcoral");}
-									"102" => {css.insert("background-color","lightgreen");}
-									"103" => {css.insert("background-color","lightyellow");}
-									"104" => {css.insert("background-color","lightskyblue");}
-									"105" => {css.insert("background-color","violet");}
-									"106" => {css.insert("background-color","lightcyan");}
-									"107" => {css.insert("background-color","white");}
-
-									// Reset fg/bg
-									"39" => {css.insert("color","unset");}
-									"49" => {css.insert("background-color","unset");}
-
-									// 256-color fg: 38;5;n
-									"38" if iter.peek() == Some(&&"5") => {
-										iter.next(); // consume "5"
-										if let Some(&n) = iter.next() {
-											if let Ok(idx) = n.parse::<u8>() {
-												let (r, g, b) = xterm256_to_rgb(idx);
-												css.insert("color", Box::leak(format!("rgb({},{},{})", r, g, b).into_boxed_str()));
-											}
-										}
-									}
-									
-									// 256-color bg: 48;5;n
-									"48" if iter.peek() == Some(&&"5") => {
-										iter.next();
-										if let Some(&n) = iter.next() {
-											if let Ok(idx) = n.parse::<u8>() {
-												let (r, g, b) = xterm256_to_rgb(idx);
-												css.insert("background-color", Box::leak(format!("rgb({},{},{})", r, g, b).into_boxed_str()));
-											}
-										}
-									}
-									
-									// TrueColor fg: 38;2;R;G;B
-									"38" if iter.peek() == Some(&&"2") => {
-										iter.next(); // consume "2"
-										let r = iter.next().and_then(|s| s.parse::<u8>().ok()).unwrap_or(0);
-										let g = iter.next().and_then(|s| s.parse::<u8>().ok()).unwrap_or(0);
-										let b = iter.next().and_then(|s| s.parse::<u8>().ok()).unwrap_or(0);
-										css.insert("color", Box::leak(format!("rgb({},{},{})", r, g, b).into_boxed_str()));
-									}
-									
-									// TrueColor bg: 48;2;R;G;B
-									"48" if iter.peek() == Some(&&"2") => {
-										iter.next();
-										let r = iter.next().and_then(|s| s.parse::<u8>().ok()).unwrap_or(0);
-										let g = iter.next().and_then(|s| s.parse::<u8>().ok()).unwrap_or(0);
-										let b = iter.next().and_then(|s| s.parse::<u8>().ok()).unwrap_or(0);
-										css.insert("background-color", Box::leak(format!("rgb({},{},{})", r, g, b).into_boxed_str()));
-									}
-
-									_ => {
-										// unknown or unsupported code, ignore
-									}
-								}
-							}
-
-							return css;
-						}
-						let css = escape_to_css(self.current_escape.clone());
-
-						// if there is empty style field at current cursor position, update its css 
-						if self.cursor_position_index < self.formated_text.len() && self.formated_text.get(self.cursor_position_index).unwrap().text.len() == 0 {
-							self.formated_text.get_mut(self.cursor_position_index).unwrap().style.extend(css);
-							if !self.formated_text.get(self.cursor_position_index).unwrap().updated {
-								self.formated_text.get_mut(self.cursor_position_index).unwrap().updated = true;
-								self.formated_text_changes += 1;
-							}
-						}
-						// create new style field and switch to it
-						else {
-							if self.cursor_position_index < self.formated_text.len() { // expected
-								self.formated_text.insert(self.cursor_position_index+1, BUFF_formated_text{text:"".to_string(),style:self.formated_text.get(self.cursor_position_index).unwrap().style.clone(),updated:true,id:0});
-								self.cursor_position_index += 1;
-								self.cursor_position_character = 0;
-							}
-							else { // handle situation when cursor position is wrong = len() is 0 (this is result of partial updates removing all segments - even the first one)
-								self.formated_text.push(BUFF_formated_text{text:"".to_string(),style:[].iter().cloned().collect(),updated:true,id:0});
-								self.cursor_position_index = self.formated_text.len()-1;
-								self.cursor_position_character = 0;
-								eprintln!("(warning)  BUFF: invalid position in buffer - reseting");
-							}
-
-							self.formated_text.get_mut(self.cursor_position_index).unwrap().style.extend(css);
-							self.formated_text_changes += 1;
-						}
-
-					}
-					
-					else if final_escape.ends_with("t") { // set window state
-						// TODO: ignore or print it
-					}
-
-					else if final_escape.ends_with("r") { // scrolling region
-						// TODO
-					}
-
-					else if final_escape.ends_with("l") || final_escape.ends_with("h") { // enable or disable features
-						
-						// while linux used sequences contain ?, windows use non standart format without it (meaning should be the same) - ie. '\x1b[?{number}h/l' or '\x1b[{number}h/l'
-						let feature_id = final_escape[if final_escape.starts_with("?"){1}else{0} .. final_escape.len()-1].parse::<u8>().unwrap_or(0);
-					
-						// TODO: actaually support them
-
-					}
-					
-					else if final_escape.ends_with("J") { // clear sequences
-						
-						// self.current_escape == "\x1b[0J" || self.current_escape == "\x1b[1J" || self.current_escape == "\x1b[2J" || self.current_escape == "\x1b[3J"
-						
-						if final_escape.ends_with("2J") || final_escape.ends_with("3J") { // entire screen
-							// TODO: there are actually some differences between these two
-							for i in (0..self.formated_text.len()) {
-								self.formated_text.get_mut(i).unwrap().text = "".to_string();
-								if !self.formated_text.get_mut(i).unwrap().updated {
-									self.formated_text.get_mut(i).unwrap().updated = true;
-									self.formated_text_changes += 1;
-								}
-							}
-							self.cursor_position_index = 0;
-							self.cursor_position_character = 0;
-						}
-						else if final_escape.ends_with("1J") { // from beginning to cursor
-							for i in (0..self.cursor_position_index) {
-								self.formated_text.get_mut(i).unwrap().text = "".to_string();
-								if !self.formated_text.get_mut(i).unwrap().updated {
-									self.formated_text.get_mut(i).unwrap().updated = true;
-									self.formated_text_changes += 1;
-								}
-							}
-							self.formated_text.get_mut(self.cursor_position_index).unwrap().text.drain(..self.cursor_position_character);
-							if !self.formated_text.get_mut(self.cursor_position_index).unwrap().updated {
-								self.formated_text.get_mut(self.cursor_position_index).unwrap().updated = true;
-								self.formated_text_changes += 1;
-							}
-							self.cursor_position_character = 0;
-						}
-						else /*final_escape.ends_with("0J") || final_escape.ends_with("J")*/ { // from end to cursor 
-							for i in (self.cursor_position_index+1..self.formated_text.len()) {
-								self.formated_text.get_mut(i).unwrap().text = "".to_string();
-								if !self.formated_text.get_mut(i).unwrap().updated {
-									self.formated_text.get_mut(i).unwrap().updated = true;
-									self.formated_text_changes += 1;
-								}
-							}
-							self.formated_text.get_mut(self.cursor_position_index).unwrap().text.truncate(self.cursor_position_character+1);
-							if !self.formated_text.get_mut(self.cursor_position_index).unwrap().updated {
-								self.formated_text.get_mut(self.cursor_position_index).unwrap().updated = true;
-								self.formated_text_changes += 1;
-							}
-						}
-
-					}
-					
-					else if final_escape.ends_with("K") { // clear line
-						
-						// get cursor position
-						let c = self.get_cursor_c();
-						let r = self.get_cursor_r();
-						
-						if final_escape == "2K" { // entire line
-							
-							// go to beginning of the line
-							self.set_cursor_cr(1,r);
-							
-							// write spaces
-							for _ in 0..self.size_columns{ self.write_buff(' '); }
-							
-						}
-						else if final_escape == "1K" { // from beginning to cursor
-							
-							// go to beginning of the line
-							self.set_cursor_cr(1,r);
-							
-							// write spaces
-							for _ in 0..c-1 { self.write_buff(' '); }
-							
-						}
-						else if final_escape == "0K" || final_escape == "K" { // from cursor to end of line
-							
-							// write spaces
-							for _ in 0..(self.size_columns+1).saturating_sub(c) { self.write_buff(' '); }
-							
-						}
-						
-						// restore cursor position
-						self.set_cursor_cr(c,r);
-
-						// end sequence
-						self.current_escape = "".to_string();
-					}
+			// plan next update
+			self.next_update = Instant::now() + Duration::from_millis(3);
 			
-					else if final_escape.ends_with("X") { // erase in line without moving cursor
-					
-						// get cursor position
-						let c = self.get_cursor_c();
-						let r = self.get_cursor_r();
-						
-						// write spaces
-						for _ in 0..final_escape[0..final_escape.len()-1].parse::<u8>().unwrap_or(1) { self.write_buff(' '); }
-						
-						// restore cursor position
-						self.set_cursor_cr(c,r);
-
-						// end sequence
-						self.current_escape = "".to_string();
-						
-					}
-					
-					else if final_escape.ends_with("H") || final_escape.ends_with("f") { // absolute cursor position
-						
-						// both coordinates given
-						if self.current_escape.contains(";") {
-							let r = self.current_escape[2..self.current_escape.find(';').unwrap()].parse::<usize>().unwrap_or(1);
-							let c = self.current_escape[self.current_escape.find(';').unwrap()+1..self.current_escape.len()-1].parse::<usize>().unwrap_or(1);
-							self.set_cursor_cr(c,self.size_rows.saturating_sub(r)+1);
-						}
-						
-						// column ommited so default
-						else {
-							let r = self.current_escape[2..self.current_escape.find('H').unwrap()].parse::<usize>().unwrap_or(1);
-							let c = 1;
-							self.set_cursor_cr(c,self.size_rows.saturating_sub(r)+1);
-						}
-						
-					}
-					
-					else if final_escape.ends_with("A") { // cursor up
-						let n = self.current_escape[2..self.current_escape.len()-1].parse::<usize>().unwrap_or(1);
-						self.set_cursor_cr(self.get_cursor_c(),self.get_cursor_r()+n);
-					}
-					
-					else if final_escape.ends_with("B") { // cursor down
-						let n = self.current_escape[2..self.current_escape.len()-1].parse::<usize>().unwrap_or(1);
-						let r = self.get_cursor_r();
-						if n <= r { self.set_cursor_cr(self.get_cursor_c(),r-n); }
-						else { self.set_cursor_cr(self.get_cursor_c(),0); }
-					}
-					
-					else if final_escape.ends_with("C") { // cursor right
-						let n = self.current_escape[2..self.current_escape.len()-1].parse::<usize>().unwrap_or(1);
-						self.set_cursor_cr(self.get_cursor_c()+n,self.get_cursor_r());
-					}
-					
-					else if final_escape.ends_with("D") { // cursor left
-						let n = self.current_escape[2..self.current_escape.len()-1].parse::<usize>().unwrap_or(1);
-						let c = self.get_cursor_c();
-						if n < c { self.set_cursor_cr(c-n,self.get_cursor_r()); }
-						else { self.set_cursor_cr(1,self.get_cursor_r()); }
-					}
-					
-					// else if ... // TODO: some more
-					
-					
-					// pre-end sequence
-                    //self.current_escape.push('\x00');
-					self.current_escape = "".to_string();
-				}
-				
-			} 
+			// update ui
+			WebView::handle_once();
 			
-
-            // enforce max length
-            if self.current_escape.len() >= self.current_escape_max_length {
-                eprintln!("(warning)  BUFF: unknown escape sequence '{}'", self.current_escape);
-
-                // print it to terminal
-                let escape = self.current_escape.get(1..).unwrap().to_owned();
-                for c in escape.chars() {
-                    //self.write_buff(c);
-                }
-				
-				// end sequence
-                self.current_escape = "".to_string();
-            }
-
+			// print debug data
+			#[cfg(debug_assertions)]
+			if delay > Duration::from_millis(3) {
+				eprintln!("(warning)  UI: update delay {:?}", delay);
+			}
+			#[cfg(debug_assertions)]
+			if start.elapsed() > Duration::from_millis(3) {
+				eprintln!("(warning)  UI: update duration {:?}", start.elapsed());
+			}
         }
+		
+	}
+	
+	fn wait (&self) { // sleep until some shell writes something or it is time to handle window events (HUI cant wake us for them)
+		if self.busy { return; }
+		let _ = self.wake.recv_timeout(self.next_update.saturating_duration_since(Instant::now()));
+	}
+	
+}
 
-    }
+
+struct TAB {
+	id: usize, // unique within the window (used for DOM ids)
+	panes: Vec<PANE>,
+	layout: TAB_layout,
+	focused: usize, // id of the pane that gets keyboard input
+	label: String, // displayed in the tab bar
+}
+impl TAB {
+	
+	fn new(options: &OPTIONS, id: usize, pane_id: usize, cwd: Option<String>, wake: &SyncSender<()>) -> Option<Self> {
+		let pane = PANE::new(options, pane_id, cwd, wake)?;
+		Some(Self { id, panes: vec![pane], layout: TAB_layout::Pane(pane_id), focused: pane_id, label: String::new() })
+	}
+	
+	fn pane(&mut self, id: usize) -> Option<&mut PANE> {
+		self.panes.iter_mut().find(|pane| pane.id == id)
+	}
+	
+	fn focused_cwd(&self) -> Option<String> { // working directory of the focused pane (new tabs and panes start there)
+		self.panes.iter().find(|pane| pane.id == self.focused)?.cwd()
+	}
+	
+	fn title(&self) -> Option<String> { // title of the focused pane (set by the application or its foreground process), None when there is none
+		let pane = self.panes.iter().find(|pane| pane.id == self.focused)?;
+		if !pane.buff.title.is_empty() { return Some(pane.buff.title.clone()); }
+		if !pane.process_name.is_empty() { return Some(pane.process_name.clone()); }
+		return None;
+	}
+	
+	fn split(&mut self, vertical: bool, pane: PANE) { // focused pane is split in half, the new one gets focus
+		if self.layout.split(self.focused, vertical, pane.id) {
+			self.focused = pane.id;
+			self.panes.push(pane);
+		}
+	}
+	
+	fn close(&mut self, id: usize) { // the pane next to the closed one takes its space (last pane can not be closed, the tab is closed instead)
+		if let Some(replacement) = self.layout.remove(id) {
+			self.panes.retain(|pane| pane.id != id);
+			if self.focused == id {
+				self.focused = replacement;
+			}
+		}
+	}
+	
+	fn focus(&mut self, direction: &str) { // focus pane next to the focused one
+		let mut rects = vec![];
+		self.layout.rects(0.0, 0.0, 1.0, 1.0, &mut rects);
+		let Some(&(_, x, y, w, h)) = rects.iter().find(|r| r.0 == self.focused) else { return; };
+		
+		// point just behind the middle of the edge
+		let (px, py) = match direction {
+			"left" => (x - 0.001, y + h/2.0),
+			"right" => (x + w + 0.001, y + h/2.0),
+			"up" => (x + w/2.0, y - 0.001),
+			"down" => (x + w/2.0, y + h + 0.001),
+			_ => return,
+		};
+		if let Some(rect) = rects.iter().find(|r| px >= r.1 && px < r.1+r.3 && py >= r.2 && py < r.2+r.4) {
+			self.focused = rect.0;
+		}
+	}
+	
+	fn resize(&mut self, direction: &str) { // move the closest border of the focused pane
+		let (vertical, delta) = match direction {
+			"left" => (true, -0.05),
+			"right" => (true, 0.05),
+			"up" => (false, -0.05),
+			"down" => (false, 0.05),
+			_ => return,
+		};
+		self.layout.resize(self.focused, vertical, delta);
+	}
+	
+}
 
 
-	fn iter_next (&self, index: &mut usize, character: &mut usize) -> bool {
-		let this = &self.formated_text;
-		
-		// same style chunk
-		*character += 1;
-		while ! this.get(*index).unwrap().text.is_char_boundary(*character) && *character < this.get(*index).unwrap().text.len() {
-			*character += 1;
-		}
-		if *character < this.get(*index).unwrap().text.len() {
-			return true;
+#[derive(Clone)]
+enum TAB_layout {
+	Pane(usize), // pane id
+	Split { vertical: bool, ratio: f32, first: Box<TAB_layout>, second: Box<TAB_layout> }, // vertical means side by side, ratio is the size of the first one
+}
+impl TAB_layout {
+	
+	fn first(&self) -> usize { // id of the first pane
+		match self {
+			TAB_layout::Pane(id) => *id,
+			TAB_layout::Split { first, .. } => first.first(),
 		}
-		
-		// next style chunk (with at least one char)
-		else { 
-			while true {
-				if ! (*index+1 < this.len()) {
-					return false;
-				}
-				*index += 1;
-				if this.get(*index).unwrap().text.len() > 0 {break;}
+	}
+	
+	fn split(&mut self, id: usize, vertical: bool, new_id: usize) -> bool {
+		match self {
+			TAB_layout::Pane(pane) if *pane == id => {
+				*self = TAB_layout::Split { vertical, ratio: 0.5, first: Box::new(TAB_layout::Pane(id)), second: Box::new(TAB_layout::Pane(new_id)) };
+				return true;
 			}
-			*character = 0;
-			return true;
+			TAB_layout::Pane(_) => return false,
+			TAB_layout::Split { first, second, .. } => return first.split(id, vertical, new_id) || second.split(id, vertical, new_id),
 		}
-		
 	}
 	
-	fn iter_prev (&self, index: &mut usize, character: &mut usize) -> bool {
-		let this = &self.formated_text;
-		
-		// prev style chunk (with at least one char)
-		if *character == 0 { 
-			while true {
-				if ! (*index > 0) {
-					return false;
-				}
-				*index -= 1;
-				if this.get(*index).unwrap().text.len() > 0 {break;}
-			}
-			*character = this.get(*index).unwrap().text.len()-1;
-			while ! this.get(*index).unwrap().text.is_char_boundary(*character) && *character > 0 {
-				*character -= 1;
-			}
-			return true;
+	fn remove(&mut self, id: usize) -> Option<usize> { // split containing the pane is replaced by the other half, returns first pane of that half
+		let TAB_layout::Split { first, second, .. } = self else { return None; };
+		if matches!(**first, TAB_layout::Pane(pane) if pane == id) {
+			*self = (**second).clone();
+			return Some(self.first());
 		}
-		
-		// same style chunk
-		else {
-			*character -= 1;
-			while ! this.get(*index).unwrap().text.is_char_boundary(*character) && *character > 0 {
-				*character -= 1;
+		if matches!(**second, TAB_layout::Pane(pane) if pane == id) {
+			*self = (**first).clone();
+			return Some(self.first());
+		}
+		return first.remove(id).or_else(|| second.remove(id));
+	}
+	
+	fn resize(&mut self, id: usize, vertical: bool, delta: f32) -> Option<bool> { // None when pane is not inside, otherwise whether some split was already resized
+		match self {
+			TAB_layout::Pane(pane) => if *pane == id { Some(false) } else { None },
+			TAB_layout::Split { vertical: split_vertical, ratio, first, second } => {
+				let resized = first.resize(id, vertical, delta).or_else(|| second.resize(id, vertical, delta))?;
+				if !resized && *split_vertical == vertical {
+					*ratio = (*ratio + delta).clamp(0.1, 0.9);
+					return Some(true);
+				}
+				Some(resized)
 			}
-			return true;
 		}
-		
 	}
 	
-	/*fn iter_valid (&self, index: &mut usize, character: &mut usize) { // TODO: use it or remove it - we expect all positions to be valid
-		let this = &self.formated_text;
-		if *index >= this.len(){
-			eprintln!("INVALID POSITION IN BUFFER - RESETING");
-			*index = this.len()-1;
-			*character = this.get(*index).unwrap().text.len()-1;
+	fn set_ratio(&mut self, path: &str, new_ratio: f32) { // path is '0' (first) or '1' (second) for each level from the top
+		let TAB_layout::Split { ratio, first, second, .. } = self else { return; };
+		match path.chars().next() {
+			None => *ratio = new_ratio.clamp(0.1, 0.9),
+			Some('0') => first.set_ratio(&path[1..], new_ratio),
+			Some(_) => second.set_ratio(&path[1..], new_ratio),
 		}
-		if *character >= this.get(*index).unwrap().text.len() {
-			eprintln!("INVALID POSITION IN BUFFER - RESETING");
-			*character = this.get(*index).unwrap().text.len()-1;
-
-			if this.get(*index).unwrap().text.len() == 0 {
-				*character=0;
-				eprintln!(" HANDLED USIZE UNDERFLOW");
-				//return false;
+	}
+	
+	fn rects(&self, x: f32, y: f32, w: f32, h: f32, rects: &mut Vec<(usize, f32, f32, f32, f32)>) { // pane positions relative to the tab (id, x, y, width, height)
+		match self {
+			TAB_layout::Pane(id) => rects.push((*id, x, y, w, h)),
+			TAB_layout::Split { vertical: true, ratio, first, second } => {
+				first.rects(x, y, w * ratio, h, rects);
+				second.rects(x + w * ratio, y, w * (1.0 - ratio), h, rects);
+			}
+			TAB_layout::Split { vertical: false, ratio, first, second } => {
+				first.rects(x, y, w, h * ratio, rects);
+				second.rects(x, y + h * ratio, w, h * (1.0 - ratio), rects);
 			}
 		}
-	}*/
-
-
-    fn set_cursor(&mut self, mut index: usize, mut character: usize) {
-		// this function expects index to be < .len() and that character to be .is_char_boundary() && < .len()
-		// is and should be used only by set_cursor_cr
-		
-		// store current style to avoid accidentaly changing it by insert
-		let current_style = self.formated_text.get(self.cursor_position_index).unwrap().style.clone();
-		
-		// fix cases when current cursor is at blank/deleted segment
-		self.iter_next(&mut index, &mut character);
-		self.iter_prev(&mut index, &mut character);
-
-        // insert first part to index+1
-        self.formated_text.insert(index+1, BUFF_formated_text{text:self.formated_text.get(index).unwrap().text.get(..character).unwrap_or("<TERMILA_PARSER_ERROR>").to_string(),style:self.formated_text.get(index).unwrap().style.clone(),updated:true,id:0});
-        // insert new patr to index+2
-        self.formated_text.insert(index+2, BUFF_formated_text{text:"".to_string(),style:current_style,updated:true,id:0});
-        // insert second part to index+3
-        self.formated_text.insert(index+3, BUFF_formated_text{text:self.formated_text.get(index).unwrap().text.get(character..).unwrap_or("<TERMILA_PARSER_ERROR>").to_string(),style:self.formated_text.get(index).unwrap().style.clone(),updated:true,id:0});
-        // remove part at index
-		self.formated_text.get_mut(index).unwrap().text = "".to_string();
-		if !self.formated_text.get_mut(index).unwrap().updated {
-			self.formated_text.get_mut(index).unwrap().updated = true;
-			self.formated_text_changes += 1;
+	}
+	
+	fn html(&self, path: &str, grow: f32) -> String { // nested flex boxes, panes are '<div class="slot">' to be replaced by their console
+		match self {
+			TAB_layout::Pane(id) => format!("<div class=\"slot\" data-pane=\"{}\" style=\"flex-grow: {}\"></div>", id, grow),
+			TAB_layout::Split { vertical, ratio, first, second } => format!(
+				"<div class=\"split{}\" style=\"flex-grow: {}\">{}<div class=\"divider\" data-path=\"{}\"></div>{}</div>",
+				if *vertical {" vertical"} else {""}, grow,
+				first.html(&format!("{}0", path), *ratio),
+				path,
+				second.html(&format!("{}1", path), 1.0 - *ratio),
+			),
 		}
+	}
+	
+}
 
-        self.cursor_position_index = index+2;
-        self.cursor_position_character = 0;
-		
-		self.formated_text_changes += 3;
-
-    }
-
-    fn get_cursor_c(&self) -> usize {
-
-        let mut column = 0;
-
-        let mut prev = true;
-        let mut index = self.cursor_position_index;
-        let mut character = self.cursor_position_character;
-        // iter_valid(& self.formated_text,& mut index,& mut character);
-        while prev {
-
-            column += 1;
-
-            prev = self.iter_prev(& mut index,& mut character);
-			
-			if self.formated_text.get(index).unwrap().text.get(character..character+1).unwrap_or("\0") == "\n" {break;}
-
-        }
-
-        return column;
-
-    }
-
-    fn get_cursor_r(&self) -> usize {
-
-        let mut row = 0;
-
-        let mut next = true;
-        let mut index = self.cursor_position_index;
-        let mut character = self.cursor_position_character;
-        // iter_valid(& self.formated_text,& mut index,& mut character);
-        while next {
-
-            if self.formated_text.get(index).unwrap().text.get(character..character+1).unwrap_or("\0") == "\n" {
-                row += 1;
-            }
-
-            next = self.iter_next(& mut index,& mut character);
-
-        }
-
-        return row;
-
-    }
 
-    fn set_cursor_cr(&mut self, mut column: usize, mut row: usize) {
-		
-		// store debug statistics
-		#[cfg(debug_assertions)]
-        let old_col = self.get_cursor_c();
-		#[cfg(debug_assertions)]
-        let old_row = self.get_cursor_r();
-		#[cfg(debug_assertions)]
-        let des_col = column;
-		#[cfg(debug_assertions)]
-        let des_row = row;
-
-
-		// limit values to terminal size
-		if column == 0 {column=1;}
-        if column >= self.size_columns {column=self.size_columns;}
-        if row >= self.size_rows {row=self.size_rows;}
-		
-
-		// add rows in case there are less of them than the requested move
-		// TODO: this is just workaround - allows for more tuis to display correctly but sometimes moves stuff completely elsewhere (consider adding newlines at i0c0 or having them there from start and hide them by the dom generator or scrollback control) 
-		let mut index = 0;
-		let mut character = 0;
-		let mut existing_rows = 1;
-		let mut iter = true;
-        while iter {
-			if self.formated_text.get(index).unwrap().text.get(character..character+1).unwrap_or("\0") == "\n" {existing_rows+=1;}
-            iter = self.iter_next(& mut index,& mut character);
-			if existing_rows > self.size_rows {break;}
-        }
-		// add newlines if needed
-		if existing_rows < row+1 {
-			self.set_cursor( self.formated_text.len()-1, self.formated_text.get(self.formated_text.len()-1).unwrap().text.len() ); // since we are in set_cursor we can move the cursor freely
-			for _ in 0..(self.size_rows-existing_rows) { self.write_buff('\n'); }
-		}
+struct PANE { // one terminal (shell with its buffer)
+	id: usize, // unique within the window (used for DOM ids)
+	buff: BUFF,
+	pty: PTY,
+	parser: PARSER,
+	exited: bool, // child exit was already reported
+	fps: u8,
+	next_update: std::time::Instant,
+	process_title: bool, // keep process_name updated
+	process_name: String, // foreground process (empty when unknown)
+	focused: bool, // last focus state (focus in and out is reported on change)
+}
+impl PANE {
+	
+	// max time spent parsing output per call (the rest waits in the pty channel, so UI and input stay responsive)
+	const PROCESS_TIME: Duration = Duration::from_millis(10);
+	
+	fn new(options: &OPTIONS, id: usize, cwd: Option<String>, wake: &SyncSender<()>) -> Option<Self> {
 		
+		// setup terminal
+		let mut pty = match PTY::new(options.shell.clone(), options.shell_args.clone(), options.term.clone(), cwd.unwrap_or(options.working_directory.clone()), wake.clone()) {
+			Some(pty) => pty,
+			None => {
+				return None;
+			}
+		};
+		let (rows, columns) = options.geometry.unwrap_or((30,100));
+		pty.set_size(rows, columns); // real size is set by UI once the window is measured
 		
-		// start at the end
-        index = self.formated_text.len()-1;
-        character = self.formated_text.get(index).unwrap().text.len();
-        //iter_valid(& self.formated_text,& mut index,& mut character);
-
-
-        // set to begining of given row
-        let mut prev = true;
-        while prev {
-
-            if self.formated_text.get(index).unwrap().text.get(character..character+1).unwrap_or("\0") == "\n" {
-				if row == 0 {break;}
-                row -= 1;
-            }
-
-            prev = self.iter_prev(& mut index,& mut character);
-
-        }
-
-
-        // set to given column if possible
-        let mut next = true;
-        while next {
-
-            next = self.iter_next(& mut index,& mut character);
-
-            column -= 1;
-            if column == 0 {break;}
-
-            if self.formated_text.get(index).unwrap().text.get(character..character+1).unwrap_or("\0") == "\n" {break;}
-
-        }
-
-
-		// finally set cursor to calculated position
-        self.set_cursor(index, character);
+		// init parsser
+		let (rows, columns) = pty.get_size();
+		let mut buff = BUFF::new(rows as usize, columns as usize, options.max_buff_size);
+		buff.console = format!("console-{}", id);
+		buff.cursor_default = match options.cursor_shape.as_str() { "underline" => 3, "bar" => 5, _ => 1 } + (options.cursor_blink == 0) as usize; // blinking or steady variant
 		
+		// set fps
+		let fps: u8 = options.fps;
 		
-		// add spaces to reach desired column outside existing text
-		self.formated_text.insert(self.cursor_position_index+1, BUFF_formated_text{text:"".to_string(),style:[].iter().cloned().collect(),updated:true,id:0}); // use neutral color
-		self.formated_text_changes += 1;
-		self.cursor_position_index += 1;
-		self.cursor_position_character = 0;
-		for _ in 0..column { self.write_buff(' '); } // add spaces without style
-		self.formated_text.insert(self.cursor_position_index+1, BUFF_formated_text{text:"".to_string(),style:self.formated_text.get(self.cursor_position_index-1).unwrap().style.clone(),updated:true,id:0}); // restore previous color
-		self.formated_text_changes += 1;
-		self.cursor_position_index += 1;
-		self.cursor_position_character = 0;
+		// set initial next update
+		let next_update = Instant::now();
 		
-
-
-		// print debug statistics
-		#[cfg(debug_assertions)]
-		{
-		let fin_col = self.get_cursor_c();
-		let fin_row = self.get_cursor_r();
-		if fin_col != des_col || fin_row != des_row { eprintln!("(error)  BUFF: set cursor position (c{},r{}) -> (c{},r{}) -> (c{},r{}) = old->desired->final", old_col,old_row, des_col,des_row, fin_col,fin_row ); }
-        else { eprintln!("(info)  BUFF: set cursor position (c{},r{}) -> (c{},r{}) -> (c{},r{}) = old->desired->final", old_col,old_row, des_col,des_row, fin_col,fin_row ); }
+		Some(Self{id, buff, pty, parser: PARSER::new(), exited: false, fps, next_update, process_title: options.process_title, process_name: String::new(), focused: false })
+	}
+	
+	fn cwd (&self) -> Option<String> { // working directory of the shell (the one reported by shell integration when it is on this machine, so it works in subshells too)
+		if !self.buff.cwd.is_empty() {
+			#[cfg(target_os = "linux")]
+			let hostname = nix::unistd::gethostname().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+			#[cfg(target_os = "windows")]
+			let hostname = env::var("COMPUTERNAME").unwrap_or_default();
+			let host = &self.buff.cwd_host;
+			if host.is_empty() || host == "localhost" || host.eq_ignore_ascii_case(&hostname) { return Some(self.buff.cwd.clone()); }
 		}
-
-    }
-
-
-    fn update_full (&mut self, webview: &HUI::WebView) { // full terminal update (slow)
+		return self.pty.cwd();
+	}
 	
-		if self.formated_text_changes == 0 { return; }  // nothing to update
-
-        // create html
-        let mut html = String::new();
-        for i in &self.formated_text {
-            html.push_str( &format!("<span style=\"{}\">{}</span>", i.style.iter().map(|(key, value)|format!("{}: {};", key, value)).collect::<Vec<String>>().join(" "), UI::escape_text(&i.text)) );
-        }
-
-        // update whole terminal content
-        let js_command = format!("document.querySelector('body p').innerHTML=`{}`;", html.replace("`","\\`"));
-        webview.call_js(&js_command, Some(false));
-
-        // TODO: clear blanks here
-
-        // autoscroll
-        webview.call_js("if (document.querySelector('#menu button#autoscroll').dataset.checked!='true') {window.scrollTo(0, document.body.scrollHeight);}", Some(false));
-		
-		self.formated_text_changes = 0; // everything is updated now 
-
-    }
-
-    fn update_partial (&mut self, webview: &HUI::WebView) { // partial terminal update (little faster)
+	fn clipboard_reply (&mut self, selection: &str, text: &str) { // answer to OSC 52 query
+		let reply = format!("\x1b]52;{};{}\x1b\\", selection, buff::base64_encode(text.as_bytes()));
+		if !self.pty.exited { self.pty.write_bytes(reply.as_bytes()); }
+	}
 	
-		if self.formated_text_changes == 0 { return; }  // nothing to update
-		
-		// clear old scrollback to save memory
+	fn report_focus (&mut self, focused: bool) { // focus in/out (DECSET 1004) when the pane gains or loses focus
+		if focused == self.focused { return; }
+		self.focused = focused;
+		if self.buff.key_modes.focus_reporting && !self.pty.exited { self.pty.write_bytes(if focused { b"\x1b[I" } else { b"\x1b[O" }); }
+	}
+	
+	fn process (&mut self, webview: &HUI::WebView/*ui: & UI*/, update_ui: bool) -> bool { // returns true when there is more output waiting
+		
+		// update size first
+		let (trows,tcolumns) = self.pty.get_size();
+		self.buff.resize(trows as usize, tcolumns as usize);
+		
+		// parse output as it arrives (DOM is updated only 'fps' times per second)
+		let deadline = Instant::now() + PANE::PROCESS_TIME;
+		let mut more = false;
+		while let Some(chunk) = self.pty.read() {
+			self.parser.advance(&mut self.buff, &chunk);
+			if Instant::now() >= deadline { more = true; break; }
+		}
 		
-		// TODO: issue - know size without iterating whole terminal or guess it
-		//unsafe{CURRENT_OPTIONS.as_ref().unwrap()}.max_buff_size
+		// answer queries found in the output
+		if !self.buff.responses.is_empty() {
+			let responses = std::mem::take(&mut self.buff.responses);
+			if !self.pty.exited { self.pty.write_bytes(&responses); }
+		}
 		
+		// tell the user when the child process is gone (only visible with --hold)
+		if self.pty.exited && !self.exited {
+			self.exited = true;
+			self.buff.synchronized = None; // unfinished frame is shown
+			self.parser.advance(&mut self.buff, b"\r\n\x1b[0m[process exited]");
+		}
 		
-		// perform update
-		if self.formated_text_changes != 0 {
-			let mut js_call = "(function(){let e;".to_string(); // store all js commands and then run them all at once (reduces calling overhead)
+		// frame of synchronized output is shown once complete (or after timeout)
+		if Instant::now() >= self.next_update && !self.buff.synchronizing() {
 			
-			for i in (0..self.formated_text.len()).rev() {
-				
-				if self.formated_text_changes == 0 { break; }  // everything was already updated
-				
-				if self.formated_text.get(i).unwrap().updated {
-					
-					if self.formated_text.get(i).unwrap().id == 0 { // add element
-						self.formated_text_last_id += 1;
-						self.formated_text.get_mut(i).unwrap().id = self.formated_text_last_id;
-						
-						js_call.push_str(
-							&format!(
-								"e = document.createElement('span'); e.id = 't-{}'; e.innerHTML=`{}`; e.style.cssText = `{}`; {}",
-								self.formated_text.get(i).unwrap().id,
-								UI::escape_text(&self.formated_text.get(i).unwrap().text),
-								self.formated_text.get(i).unwrap().style.iter().map(|(key, value)|format!("{}: {};", key, value)).collect::<Vec<String>>().join(" "),
-								if self.formated_text.get(i+1).unwrap_or(&BUFF_formated_text{text:"".to_string(),style:[].iter().cloned().collect(),updated:false,id:0}).id != 0 {format!("document.querySelector('body p#console span#t-{}').before(e);", self.formated_text.get(i+1).unwrap().id)} else {"document.querySelector('body p#console').appendChild(e);".to_string()}
-							)
-						);
-						
-						self.formated_text.get_mut(i).unwrap().updated = false;
-					}
-					
-					else if self.formated_text.get(i).unwrap().text == "" && self.cursor_position_index != i { // delete element
-						// we need to avoid removing chunk at cursor position in order to preserve current color - thus just process it as edit (in case it previously contained something), this way we loose the chance to remove all possible empty chunks
-						
-						js_call.push_str(
-							&format!(
-								"document.querySelector('body p#console span#t-{}').remove();",
-								self.formated_text.get(i).unwrap().id,
-							)
-						);
-						if self.cursor_position_index > i {
-							self.cursor_position_index -= 1;
-						}
-						self.formated_text.remove(i); // should be safe to do since we iterate from the end
-					}
-					
-					else { // edit element
-						js_call.push_str(
-							&format!(
-								"e = document.querySelector('body p#console span#t-{}'); e.innerHTML=`{}`; e.style.cssText = `{}`;",
-								self.formated_text.get(i).unwrap().id,
-								UI::escape_text(&self.formated_text.get(i).unwrap().text),
-								self.formated_text.get(i).unwrap().style.iter().map(|(key, value)|format!("{}: {};", key, value)).collect::<Vec<String>>().join(" ")
-							) 
-						);
-						
-						self.formated_text.get_mut(i).unwrap().updated = false;
-					}
-					
-					self.formated_text_changes-=1;
+			// store debug data
+			#[cfg(debug_assertions)]
+			let delay = self.next_update.elapsed();
+			#[cfg(debug_assertions)]
+			let start = Instant::now();
+			
+			// plan next update
+			self.next_update = Instant::now() + Duration::from_millis((1000u64 / self.fps as u64));
+			
+			// foreground process changes without any output, so it is checked with the updates
+			if self.process_title {
+				self.process_name = self.pty.process_name().unwrap_or_default();
+			}
+			
+			// update UI
+			if update_ui {
+				//webview.call_js(&self.buff.update_full(), Some(false));
+				if let Some(js) = self.buff.update_partial() {
+					webview.call_js(&js, Some(false));
 				}
-				
 			}
-			js_call.push_str("})()");
-			webview.call_js(&js_call, Some(false));
-		}
-		
-		if self.formated_text_changes > 0 { 
-			eprintln!("(warning)  BUFF: changes updated don't match changes made"); 
-			#[cfg(not(debug_assertions))] 	
-			{ self.formated_text_changes = 0; }
-		}
-		
-		
-        // autoscroll
-        webview.call_js("if (document.querySelector('#menu button#autoscroll').dataset.checked!='true') {window.scrollTo(0, document.body.scrollHeight);}", Some(false));
-		
-    }
-
-	/*
-	positioning specs:
-
-		terminal specification:
-			column: left to right, starts at 1, values over size are interpreted as max
-			row: top to bottom, starts at 1, values over size are interpreted as max
 			
-		set position methods:
-			column: (same as terminal specification)
-			row: bottom to top, starts at 0, values over size are interpreted as max (termsize-xtermpos, NOT termsize-xtermpos+1) - code: absolute position csi, escape up/down A/B, \r, \n
-
-		dom structure:
-			index: index in vector of styled text chunks, should always point to existing one
-			character: index where next character will go - either existing position or len value (note: this is u8 byte index not nth char index)
-			(cursor position is the character to be overwriten or nul char if at he end of char array)
-
-	*/
+			// print debug data
+			#[cfg(debug_assertions)]
+			if delay > Duration::from_millis((1000u64 / self.fps as u64)) {
+				eprintln!("(warning)  PANE: update delay {:?}", delay);
+			}
+			#[cfg(debug_assertions)]
+			if start.elapsed() > Duration::from_millis((1000u64 / self.fps as u64)) {
+				eprintln!("(warning)  PANE: update duration {:?}", start.elapsed());
+			}
+			
+        }
+		
+		return more;
+	}
 
 }
 
@@ -1776,13 +2063,21 @@ impl BUFF<'_> {
 struct PTY {
     master: RawFd,
     slave: RawFd,
+    pid: pid_t, // child process
+    output: Receiver<Vec<u8>>, // chunks from the reader thread
 
     rows: usize,
     columns: usize, 
+	
+	exited: bool, // child process exited (nothing more to read)
 }
 #[cfg(target_os = "linux")]
 impl PTY {
-    fn new (shell: String, shell_args: Vec<String>, term: String) -> Option<Self> {
+    const CHUNK_SIZE: usize = 16384; // max bytes per read
+    const CHANNEL_SIZE: usize = 64; // max chunks waiting to be processed (then the reader thread and so the child process waits)
+    const HANGUP_TIMEOUT: Duration = Duration::from_secs(2); // closed child gets this long to exit after SIGHUP, then it is killed
+
+    fn new (shell: String, shell_args: Vec<String>, term: String, cwd: String, wake: SyncSender<()>) -> Option<Self> {
         unsafe {
             // open PTY master device (using BSD-style management)
             let master = posix_openpt(O_RDWR | O_NOCTTY);
@@ -1834,10 +2129,10 @@ impl PTY {
                 dup2(slave_fd, STDERR_FILENO);
                 close(slave_fd);
 
-                Command::new( shell )
-                    .env("TERM", term )
-                    .args( shell_args )
-                    .exec();
+                let mut command = Command::new( shell );
+                command.env("TERM", term ).args( shell_args );
+                if !cwd.is_empty() { command.current_dir( cwd ); }
+                command.exec();
                 std::process::exit(1); // return false;
             }
             else { // pid < 0
@@ -1845,7 +2140,23 @@ impl PTY {
                 //return true;
             }
 
-            Some(Self { master: master, slave: slave_fd, rows: 99999, columns: 99999})
+            // reader thread (blocking reads, woken UI gets the output through bounded channel)
+            let reader = dup(master);
+            let (sender, output) = mpsc::sync_channel::<Vec<u8>>(PTY::CHANNEL_SIZE);
+            thread::spawn(move || {
+                let mut buf = vec![0u8; PTY::CHUNK_SIZE];
+                loop {
+                    let n = read(reader, buf.as_mut_ptr() as *mut _, buf.len());
+                    if n < 0 && io::Error::last_os_error().raw_os_error() == Some(EINTR) { continue; }
+                    if n <= 0 { break; } // child process exited (all slave ends are closed)
+                    if sender.send(buf[..n as usize].to_vec()).is_err() { break; } // pty was dropped
+                    let _ = wake.try_send(());
+                }
+                close(reader);
+                let _ = wake.try_send(()); // UI notices the exit
+            });
+
+            Some(Self { master: master, slave: slave_fd, pid: pid, output: output, rows: 99999, columns: 99999, exited: false })
         }
     }
 
@@ -1880,40 +2191,69 @@ impl PTY {
         return true;
     }
 
-    fn read(&mut self) -> u8 {
-
-        let mut readfds: fd_set = unsafe { std::mem::zeroed() };
-        unsafe {
-            FD_ZERO(&mut readfds);
-            FD_SET(self.master, &mut readfds);
+    fn write_bytes(&mut self, bytes: &[u8]) -> bool { // whole sequence at once (so the application doesnt get part of it)
+        let mut written = 0;
+        while written < bytes.len() {
+            let n = unsafe { write(self.master, bytes[written..].as_ptr() as *const _, bytes.len() - written) };
+            if n < 0 {
+                if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted { continue; }
+                eprintln!("(error)  PTY: write failed");
+                return false;
+            }
+            written += n as usize;
         }
+        return true;
+    }
 
-        let mut timeout = timeval { tv_sec: 0, tv_usec: 0 };
-        let ready = unsafe { select(self.master + 1, &mut readfds, ptr::null_mut(), ptr::null_mut(), &mut timeout) };
-        if ready < 0 {
-            eprintln!("(error)  PTY: select()");
-        }
+    fn cwd(&self) -> Option<String> { // current directory of the shell
+        std::fs::read_link(format!("/proc/{}/cwd", self.pid)).ok().map(|path| path.to_string_lossy().to_string())
+    }
 
-        if unsafe { FD_ISSET(self.master, &readfds) } {
-            let mut buf = [0u8; 1];
-            let n = unsafe { read(self.master, buf.as_mut_ptr() as *mut _, 1) };
-            if n <= 0 {
-                eprintln!("(info)  PTY: nothing to read or error");
-                std::process::exit(0);
-                //return 255; // unused by utf8, here means exit
-            }
+    fn process_name(&self) -> Option<String> { // name of the foreground process group leader (the shell itself while it waits for a command)
+        let pgid = unsafe { tcgetpgrp(self.master) };
+        if pgid <= 0 { return None; }
+        let name = std::fs::read_to_string(format!("/proc/{}/comm", pgid)).ok()?;
+        return Some(name.trim_end().to_string());
+    }
 
-            eprintln!("(info)  PTY: read '{}' {}", if ((32..128).contains(&buf[0])) {format!("{}", buf[0] as char)} else {format!("\\x{:02x}", buf[0])}, buf[0]);
-            return buf[0];
+    fn read(&mut self) -> Option<Vec<u8>> { // next chunk of output (non-blocking), None when there is nothing new
 
+        match self.output.try_recv() {
+            Ok(chunk) => {
+                eprintln!("(info)  PTY: read {} bytes", chunk.len());
+                return Some(chunk);
+            }
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => { // reader thread ended and everything was read
+                if !self.exited { eprintln!("(info)  PTY: nothing to read or error - child process exited"); }
+                self.exited = true;
+                return None;
+            }
         }
-
-        // there are no new bytes (better would be to run this in other thread and wait for new bytes - pass nullptr instead of timeout to select)
-        //eprintln!("(info)  PTY: nothing to read"); // avoid spaming console
-        return 0;
     }
 
 }
+#[cfg(target_os = "linux")]
+impl Drop for PTY {
+    fn drop(&mut self) { // closing tab: hang up the child and collect it in background (killed when it ignores the hangup)
+        unsafe {
+            close(self.master);
+            kill(self.pid, SIGHUP);
+        }
+        let pid = self.pid;
+        thread::spawn(move || {
+            let deadline = Instant::now() + PTY::HANGUP_TIMEOUT;
+            while Instant::now() < deadline {
+                if unsafe { waitpid(pid, ptr::null_mut(), WNOHANG) } != 0 { return; } // collected (or error, ie. already collected)
+                thread::sleep(Duration::from_millis(50));
+            }
+            unsafe {
+                kill(pid, SIGKILL);
+                waitpid(pid, ptr::null_mut(), 0);
+            }
+        });
+    }
+}
 
 
 #[cfg(target_os = "windows")]
@@ -1926,11 +2266,17 @@ struct PTY {
 	rows: usize,
     columns: usize,
 	
-	write_cache: Vec<u8>
+	write_cache: Vec<u8>,
+	
+	output: Receiver<Vec<u8>>, // chunks from the reader thread
+	exited: bool, // child process exited (nothing more to read)
 }
 #[cfg(target_os = "windows")]
 impl PTY {
-    fn new (shell: String, shell_args: Vec<String>, term: String) -> Option<Self> {
+	const CHUNK_SIZE: usize = 16384; // max bytes per read
+	const CHANNEL_SIZE: usize = 64; // max chunks waiting to be processed (then the reader thread and so the child process waits)
+
+    fn new (shell: String, shell_args: Vec<String>, term: String, cwd: String, wake: SyncSender<()>) -> Option<Self> {
         unsafe {
 			
 			// create input and output pipes
@@ -2023,6 +2369,7 @@ impl PTY {
             let mut pi: PROCESS_INFORMATION = std::mem::zeroed();
 			let mut cmdline = format!("\"{}\" \"{}\"", shell, shell_args.join("\" \""));
             let mut cmd_w: Vec<u16> = cmdline .encode_utf16().chain(std::iter::once(0)).collect();
+            let cwd_w: Vec<u16> = cwd.encode_utf16().chain(std::iter::once(0)).collect();
             if CreateProcessW(
                 PCWSTR::null(),
                 PWSTR(cmd_w.as_mut_ptr()),
@@ -2031,7 +2378,7 @@ impl PTY {
                 false, // inherit handles
                 EXTENDED_STARTUPINFO_PRESENT,
                 None,
-                PCWSTR(ptr::null()),
+                if cwd.is_empty() { PCWSTR(ptr::null()) } else { PCWSTR(cwd_w.as_ptr()) },
                 &mut si_ex.StartupInfo,
                 &mut pi,
             ).is_err() {
@@ -2044,7 +2391,22 @@ impl PTY {
             DeleteProcThreadAttributeList(si_ex.lpAttributeList);
             windows::Win32::System::Memory::HeapFree( heap, windows::Win32::System::Memory::HEAP_FLAGS(0), Some(si_ex.lpAttributeList.0 as *mut _) );
 			
-            Some( Self{ in_write, out_read, hpc, pi, rows: 999, columns: 999, write_cache: vec![], })
+			// reader thread (blocking reads, woken UI gets the output through bounded channel)
+			let (sender, output) = mpsc::sync_channel::<Vec<u8>>(PTY::CHANNEL_SIZE);
+			let out_read_raw = out_read.0 as usize; // handles cant be sent to other thread
+			thread::spawn(move || {
+				let out_read = HANDLE(out_read_raw as *mut c_void);
+				let mut buf = vec![0u8; PTY::CHUNK_SIZE];
+				loop {
+					let mut read = 0u32;
+					if unsafe { ReadFile(out_read, Some(&mut buf), Some(&mut read), None) }.is_err() || read == 0 { break; } // pipe closed
+					if sender.send(buf[..read as usize].to_vec()).is_err() { break; } // pty was dropped
+					let _ = wake.try_send(());
+				}
+				let _ = wake.try_send(()); // UI notices the exit
+			});
+			
+            Some( Self{ in_write, out_read, hpc, pi, rows: 999, columns: 999, write_cache: vec![], output, exited: false, })
         }
     }
 
@@ -2099,70 +2461,78 @@ impl PTY {
 		}
     }
 
-    fn read(&mut self) -> u8 {
-        unsafe {
-			
-			// check if the child process is still running
-			let mut code = 0u32;
-            if GetExitCodeProcess(self.pi.hProcess, &mut code).is_ok() {
-                if code != STILL_ACTIVE.0 as u32 {
-					eprintln!("(info)  PTY: child process exited");
-					
-					// clean exit (code previously in Drop)
-					unsafe {
-						CloseHandle(self.in_write);
-						CloseHandle(self.out_read);
-						if self.pi.hProcess.0 != ptr::null_mut() {
-							CloseHandle(self.pi.hProcess);
-						}
-						if self.pi.hThread.0 != ptr::null_mut() {
-							CloseHandle(self.pi.hThread);
-						}
-						if self.hpc.0 != 0 {
-							ClosePseudoConsole(self.hpc);
-						}
-					}
-					
-					std::process::exit(0);
-					//return 255; // unused by utf8, here means exit
-				} 
-            }
-			
-            // check if there’s a byte available (non-blocking)
-            let mut avail = 0u32;
-            let ok = !PeekNamedPipe(
-                self.out_read,
-                None,
-                0,
-                None,
-                Some(&mut avail),
-                None,
-            ).is_err();
-            if !ok {
-                return 0; // other error occured
-            }
-			if avail == 0 {
-				return 0; // there were no new bytes
+    fn write_bytes(&mut self, bytes: &[u8]) -> bool { // whole sequence at once (conpty needs it this way)
+		unsafe {
+			if WriteFile(self.in_write, Some(bytes), None, None).is_err() {
+				eprintln!("(error)  PTY: WriteFile");
+				return false;
 			}
+		}
+		return true;
+    }
 
-			// read from the console
-            let mut buf = [0u8; 1];
-            let mut read = 0u32;
-            if ReadFile(
-                self.out_read,
-                Some(&mut buf), // output buffer
-                Some(&mut read), // number of bytes read
-                None,
-				).is_ok() && read == 1 {
-					eprintln!("(info)  PTY: read '{}' {}", if ((32..128).contains(&buf[0])) {format!("{}", buf[0] as char)} else {format!("\\x{:02x}", buf[0])}, buf[0]);
-					return buf[0]; // there were bytes to read
-            }
-			
-            return 0; // other error occured
-			
-        }
+    fn cwd(&self) -> Option<String> {
+		// TODO: current directory of another process is not available on windows (without reading its memory)
+		return None;
+    }
+
+    fn process_name(&self) -> Option<String> {
+		// TODO: conpty doesnt tell which process is in foreground
+		return None;
+    }
+
+    fn read(&mut self) -> Option<Vec<u8>> { // next chunk of output (non-blocking), None when there is nothing new
+        if self.exited { return None; }
+		
+		match self.output.try_recv() {
+			Ok(chunk) => {
+				eprintln!("(info)  PTY: read {} bytes", chunk.len());
+				return Some(chunk);
+			}
+			Err(TryRecvError::Empty) => {}
+			Err(TryRecvError::Disconnected) => { // reader thread ended and everything was read
+				eprintln!("(info)  PTY: output closed");
+				self.close();
+				self.exited = true;
+				return None;
+			}
+		}
+		
+		// conpty keeps the output open after the child exits, so check if it is still running
+		let mut code = 0u32;
+		if unsafe { GetExitCodeProcess(self.pi.hProcess, &mut code) }.is_ok() && code != STILL_ACTIVE.0 as u32 {
+			eprintln!("(info)  PTY: child process exited");
+			self.close();
+			self.exited = true;
+		}
+		
+		return None;
     }
 	
+	fn close(&mut self) { // clean exit (code previously in Drop)
+		unsafe {
+			CloseHandle(self.in_write);
+			CloseHandle(self.out_read);
+			if self.pi.hProcess.0 != ptr::null_mut() {
+				CloseHandle(self.pi.hProcess);
+			}
+			if self.pi.hThread.0 != ptr::null_mut() {
+				CloseHandle(self.pi.hThread);
+			}
+			if self.hpc.0 != 0 {
+				ClosePseudoConsole(self.hpc); // also ends the child when it is still running
+			}
+		}
+	}
+	
+}
+#[cfg(target_os = "windows")]
+impl Drop for PTY {
+	fn drop(&mut self) { // closing tab
+		if !self.exited {
+			self.close();
+		}
+	}
 }
 #[cfg(target_os = "windows")]
 unsafe impl Send for PTY {}
@@ -2174,7 +2544,13 @@ unsafe impl Sync for PTY {}
 fn main() {
 	
 	// load options
-	let options = OPTIONS::new();
+	let options = match OPTIONS::new() {
+		Ok(options) => options,
+		Err(err) => {
+			std::eprintln!("termila: {}", err); // always shown (even in release builds)
+			std::process::exit(1);
+		}
+	};
 
     // init UI
 	let mut ui = UI::new(options);
@@ -2186,7 +2562,7 @@ fn main() {
 		ui.handle();
 		
 		// save cpu time
-		thread::sleep(Duration::from_millis(1)); 
+		ui.wait();
 		
 	}
 
@@ -0,0 +1,282 @@
+// DEC/ANSI escape sequence parser
+// (state machine from https://vt100.net/emu/dec_ansi_parser with streaming utf-8 decoding, C1 controls and ':' sub-parameters)
+// it only splits the byte stream into characters, controls and sequences - what they mean is up to the PARSER_handler
+
+
+// receives everything recognized by the parser
+pub trait PARSER_handler {
+
+	// printable character
+	fn print(&mut self, chr: char);
+
+	// C0 control (C1 controls are passed to esc_dispatch as their 7-bit 'ESC Fe' equivalents)
+	fn execute(&mut self, byte: u8);
+
+	// 'ESC <intermediates> <final>'
+	fn esc_dispatch(&mut self, intermediates: &[u8], byte: u8);
+
+	// 'CSI <params> <intermediates> <final>', private markers ('?', '>', '=', '<') are the first intermediate
+	// params are ';' separated values and each of them has at least one ':' separated sub-parameter, missing values are 0 (so there is always at least one)
+	fn csi_dispatch(&mut self, params: &[Vec<usize>], intermediates: &[u8], byte: u8);
+
+	// 'OSC <data> ST' (or BEL)
+	fn osc_dispatch(&mut self, data: &str);
+
+	// 'DCS <params> <intermediates> <final> <data> ST'
+	fn dcs_dispatch(&mut self, _params: &[Vec<usize>], _intermediates: &[u8], _byte: u8, _data: &str) {}
+
+}
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum PARSER_state {
+	Ground,
+	Escape,
+	EscapeIntermediate,
+	CsiEntry,
+	CsiParam,
+	CsiIntermediate,
+	CsiIgnore,
+	DcsEntry,
+	DcsParam,
+	DcsIntermediate,
+	DcsPassthrough,
+	DcsIgnore,
+	OscString,
+	SosPmApcString, // SOS, PM and APC strings are ignored
+}
+
+
+pub struct PARSER {
+	state: PARSER_state,
+
+	intermediates: Vec<u8>,
+	params: Vec<Vec<usize>>,
+	overflow: bool, // separator was dropped, digits until the next stored one are ignored
+	data: String, // OSC and DCS string
+	dcs_final: u8,
+
+	utf8_char: u32, // code point decoded so far
+	utf8_needed: u8, // continuation bytes still missing
+	utf8_min: u32, // smallest code point for the sequence length (longer encodings are invalid)
+}
+impl PARSER {
+
+	const MAX_PARAMS: usize = 32;
+	const MAX_SUBPARAMS: usize = 8; // per parameter (colors need 6)
+	const MAX_PARAM_VALUE: usize = 65535;
+	const MAX_INTERMEDIATES: usize = 4;
+	const MAX_STRING: usize = 1 << 20; // longer OSC/DCS data is cut (OSC 52 clipboard can be big)
+
+	pub fn new() -> Self {
+		Self {
+			state: PARSER_state::Ground,
+			intermediates: vec![],
+			params: vec![],
+			overflow: false,
+			data: String::new(),
+			dcs_final: 0,
+			utf8_char: 0,
+			utf8_needed: 0,
+			utf8_min: 0,
+		}
+	}
+
+	// feed bytes (incomplete utf-8 character at the end is finished by the next call)
+	pub fn advance(&mut self, handler: &mut dyn PARSER_handler, bytes: &[u8]) {
+		for &byte in bytes {
+
+			// continuation of multibyte character
+			if self.utf8_needed > 0 {
+				if byte & 0xC0 == 0x80 {
+					self.utf8_char = (self.utf8_char << 6) | (byte & 0x3F) as u32;
+					self.utf8_needed -= 1;
+					if self.utf8_needed == 0 {
+						let chr = Some(self.utf8_char).filter(|c| *c >= self.utf8_min).and_then(char::from_u32).unwrap_or('\u{FFFD}'); // overlong encoding or surrogate
+						self.input(handler, chr);
+					}
+					continue;
+				}
+				// sequence was cut, the byte is processed on its own
+				self.utf8_needed = 0;
+				self.input(handler, '\u{FFFD}');
+			}
+
+			match byte {
+				0x00..=0x7F => self.input(handler, byte as char),
+				0xC2..=0xDF => { self.utf8_char = (byte & 0x1F) as u32; self.utf8_needed = 1; self.utf8_min = 0x80; }
+				0xE0..=0xEF => { self.utf8_char = (byte & 0x0F) as u32; self.utf8_needed = 2; self.utf8_min = 0x800; }
+				0xF0..=0xF4 => { self.utf8_char = (byte & 0x07) as u32; self.utf8_needed = 3; self.utf8_min = 0x10000; }
+				_ => self.input(handler, '\u{FFFD}'), // continuation byte without start or invalid byte
+			}
+		}
+	}
+
+	fn input(&mut self, handler: &mut dyn PARSER_handler, chr: char) {
+		let c = chr as u32;
+
+		// transitions from anywhere
+		match c {
+			0x18 | 0x1A => { // CAN, SUB (cancel sequence)
+				handler.execute(c as u8);
+				self.state = PARSER_state::Ground;
+				return;
+			}
+			0x1B => { // ESC
+				self.finish_string(handler);
+				self.enter(PARSER_state::Escape);
+				return;
+			}
+			0x80..=0x9F => { // C1 controls
+				self.finish_string(handler);
+				match c {
+					0x90 => self.enter(PARSER_state::DcsEntry),
+					0x9B => self.enter(PARSER_state::CsiEntry),
+					0x9D => self.enter(PARSER_state::OscString),
+					0x98 | 0x9E | 0x9F => self.enter(PARSER_state::SosPmApcString),
+					0x9C => self.state = PARSER_state::Ground, // ST
+					_ => {
+						handler.esc_dispatch(&[], (c - 0x40) as u8);
+						self.state = PARSER_state::Ground;
+					}
+				}
+				return;
+			}
+			_ => {}
+		}
+
+		// C0 controls are executed in the middle of sequences too (except strings)
+		let control = c < 0x20;
+
+		match self.state {
+
+			PARSER_state::Ground => {
+				if control { handler.execute(c as u8); }
+				else if c != 0x7F { handler.print(chr); }
+			}
+
+			PARSER_state::Escape => {
+				match c {
+					_ if control => handler.execute(c as u8),
+					0x20..=0x2F => { self.collect(c); self.state = PARSER_state::EscapeIntermediate; }
+					0x50 => self.enter(PARSER_state::DcsEntry), // P
+					0x5B => self.enter(PARSER_state::CsiEntry), // [
+					0x5D => self.enter(PARSER_state::OscString), // ]
+					0x58 | 0x5E | 0x5F => self.enter(PARSER_state::SosPmApcString), // X ^ _
+					0x30..=0x7E => { handler.esc_dispatch(&[], c as u8); self.state = PARSER_state::Ground; }
+					0x7F => {}
+					_ => self.state = PARSER_state::Ground, // not a sequence
+				}
+			}
+
+			PARSER_state::EscapeIntermediate => {
+				match c {
+					_ if control => handler.execute(c as u8),
+					0x20..=0x2F => self.collect(c),
+					0x30..=0x7E => { handler.esc_dispatch(&self.intermediates, c as u8); self.state = PARSER_state::Ground; }
+					0x7F => {}
+					_ => self.state = PARSER_state::Ground,
+				}
+			}
+
+			PARSER_state::CsiEntry | PARSER_state::CsiParam | PARSER_state::CsiIntermediate => {
+				match c {
+					_ if control => handler.execute(c as u8),
+					0x30..=0x3B if self.state != PARSER_state::CsiIntermediate => { self.param(c); self.state = PARSER_state::CsiParam; }
+					0x3C..=0x3F if self.state == PARSER_state::CsiEntry => { self.collect(c); self.state = PARSER_state::CsiParam; }
+					0x20..=0x2F => { self.collect(c); self.state = PARSER_state::CsiIntermediate; }
+					0x40..=0x7E => {
+						if self.params.is_empty() { self.params.push(vec![0]); }
+						handler.csi_dispatch(&self.params, &self.intermediates, c as u8);
+						self.state = PARSER_state::Ground;
+					}
+					0x7F => {}
+					_ => self.state = PARSER_state::CsiIgnore, // private marker in the middle, parameter after intermediate or non-ascii character
+				}
+			}
+
+			PARSER_state::CsiIgnore => {
+				match c {
+					_ if control => handler.execute(c as u8),
+					0x40..=0x7E => self.state = PARSER_state::Ground,
+					_ => {}
+				}
+			}
+
+			PARSER_state::DcsEntry | PARSER_state::DcsParam | PARSER_state::DcsIntermediate => {
+				match c {
+					_ if control => {}
+					0x30..=0x3B if self.state != PARSER_state::DcsIntermediate => { self.param(c); self.state = PARSER_state::DcsParam; }
+					0x3C..=0x3F if self.state == PARSER_state::DcsEntry => { self.collect(c); self.state = PARSER_state::DcsParam; }
+					0x20..=0x2F => { self.collect(c); self.state = PARSER_state::DcsIntermediate; }
+					0x40..=0x7E => { self.dcs_final = c as u8; self.state = PARSER_state::DcsPassthrough; }
+					0x7F => {}
+					_ => self.state = PARSER_state::DcsIgnore,
+				}
+			}
+
+			PARSER_state::DcsPassthrough => {
+				if c != 0x7F && self.data.len() < PARSER::MAX_STRING { self.data.push(chr); }
+			}
+
+			PARSER_state::OscString => {
+				if c == 0x07 { // BEL terminates too (xterm)
+					self.finish_string(handler);
+					self.state = PARSER_state::Ground;
+				}
+				else if !control && self.data.len() < PARSER::MAX_STRING { self.data.push(chr); }
+			}
+
+			PARSER_state::DcsIgnore | PARSER_state::SosPmApcString => {}
+
+		}
+	}
+
+	fn enter(&mut self, state: PARSER_state) { // start new sequence
+		self.state = state;
+		self.intermediates.clear();
+		self.params.clear();
+		self.overflow = false;
+		self.data.clear();
+	}
+
+	fn collect(&mut self, c: u32) {
+		if self.intermediates.len() < PARSER::MAX_INTERMEDIATES { self.intermediates.push(c as u8); }
+	}
+
+	fn param(&mut self, c: u32) {
+		if self.params.is_empty() { self.params.push(vec![0]); }
+		match c {
+			0x3B => { // ';' next parameter
+				if self.params.len() < PARSER::MAX_PARAMS { self.params.push(vec![0]); self.overflow = false; }
+				else { self.overflow = true; }
+			}
+			0x3A => { // ':' next sub-parameter
+				let param = self.params.last_mut().unwrap();
+				if param.len() < PARSER::MAX_SUBPARAMS && !self.overflow { param.push(0); }
+				else { self.overflow = true; }
+			}
+			_ => { // digit (extra parameters don't run into the last one)
+				if self.overflow { return; }
+				let value = self.params.last_mut().unwrap().last_mut().unwrap();
+				*value = (*value * 10 + (c - 0x30) as usize).min(PARSER::MAX_PARAM_VALUE);
+			}
+		}
+	}
+
+	fn finish_string(&mut self, handler: &mut dyn PARSER_handler) { // OSC and DCS are dispatched once they end
+		match self.state {
+			PARSER_state::OscString => handler.osc_dispatch(&self.data),
+			PARSER_state::DcsPassthrough => {
+				if self.params.is_empty() { self.params.push(vec![0]); }
+				handler.dcs_dispatch(&self.params, &self.intermediates, self.dcs_final, &self.data);
+			}
+			_ => {}
+		}
+	}
+
+}
+
+
+#[cfg(test)]
+mod tests;
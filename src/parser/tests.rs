@@ -0,0 +1,83 @@
+// parser unit tests (what the handler receives for given bytes)
+
+use super::*;
+
+// records everything as text, printable characters are joined
+#[derive(Default)]
+struct RECORDER {
+	events: Vec<String>,
+}
+impl PARSER_handler for RECORDER {
+	fn print(&mut self, chr: char) {
+		match self.events.last_mut() {
+			Some(last) if last.starts_with('"') => { last.pop(); last.push(chr); last.push('"'); }
+			_ => self.events.push(format!("\"{}\"", chr)),
+		}
+	}
+	fn execute(&mut self, byte: u8) { self.events.push(format!("execute {:#04x}", byte)); }
+	fn esc_dispatch(&mut self, intermediates: &[u8], byte: u8) { self.events.push(format!("esc {}{}", String::from_utf8_lossy(intermediates), byte as char)); }
+	fn csi_dispatch(&mut self, params: &[Vec<usize>], intermediates: &[u8], byte: u8) { self.events.push(format!("csi {}{:?}{}", String::from_utf8_lossy(intermediates), params, byte as char)); }
+	fn osc_dispatch(&mut self, data: &str) { self.events.push(format!("osc {}", data)); }
+	fn dcs_dispatch(&mut self, params: &[Vec<usize>], intermediates: &[u8], byte: u8, data: &str) { self.events.push(format!("dcs {}{:?}{} {}", String::from_utf8_lossy(intermediates), params, byte as char, data)); }
+}
+
+fn parse(chunks: &[&[u8]]) -> Vec<String> {
+	let mut parser = PARSER::new();
+	let mut recorder = RECORDER::default();
+	for chunk in chunks { parser.advance(&mut recorder, chunk); }
+	recorder.events
+}
+
+#[test]
+fn text_and_controls() {
+	assert_eq!(parse(&[b"ab\r\ncd\x07"]), vec!["\"ab\"", "execute 0x0d", "execute 0x0a", "\"cd\"", "execute 0x07"]);
+}
+
+#[test]
+fn utf8_split_between_reads() {
+	let text = "ž€😀".as_bytes();
+	let chunks: Vec<&[u8]> = text.chunks(1).collect();
+	assert_eq!(parse(&chunks), vec!["\"ž€😀\""]);
+}
+
+#[test]
+fn invalid_utf8_is_replaced() {
+	// lone continuation byte, overlong '/', cut sequence followed by ascii
+	assert_eq!(parse(&[b"a\x80b\xC0\xAFc\xE2\x82d"]), vec!["\"a\u{FFFD}b\u{FFFD}\u{FFFD}c\u{FFFD}d\""]);
+}
+
+#[test]
+fn csi_params() {
+	assert_eq!(parse(&[b"\x1b[m"]), vec!["csi [[0]]m"]);
+	assert_eq!(parse(&[b"\x1b[1;;31m"]), vec!["csi [[1], [0], [31]]m"]);
+	assert_eq!(parse(&[b"\x1b[38:2::255:0:0m"]), vec!["csi [[38, 2, 0, 255, 0, 0]]m"]);
+	assert_eq!(parse(&[b"\x1b[?1049h"]), vec!["csi ?[[1049]]h"]);
+	assert_eq!(parse(&[b"\x1b[2 q"]), vec!["csi  [[2]]q"]);
+	assert_eq!(parse(&[b"\x1b[99999999H"]), vec!["csi [[65535]]H"]);
+	let long = format!("\x1b[1{};2m", ":1".repeat(100000));
+	assert_eq!(parse(&[long.as_bytes()]), vec!["csi [[1, 1, 1, 1, 1, 1, 1, 1], [2]]m"]); // sub-parameters are capped too (extra ones are dropped)
+	let many = format!("\x1b[{}5m", "1;".repeat(40));
+	assert_eq!(parse(&[many.as_bytes()]), vec![format!("csi {:?}m", vec![vec![1]; 32])]); // parameters after the 32nd are dropped, not appended to it
+	assert_eq!(parse(&[b"\xc2\x9b5A"]), vec!["csi [[5]]A"]); // C1 CSI (utf-8 encoded)
+}
+
+#[test]
+fn controls_inside_sequences() {
+	assert_eq!(parse(&[b"\x1b[1\n2H"]), vec!["execute 0x0a", "csi [[12]]H"]);
+	assert_eq!(parse(&[b"\x1b[1\x18A"]), vec!["execute 0x18", "\"A\""]); // CAN cancels
+	assert_eq!(parse(&[b"\x1b[1;2\x1b[3A"]), vec!["csi [[3]]A"]); // ESC starts new sequence
+	assert_eq!(parse(&[b"\x1b[1?2Ax"]), vec!["\"x\""]); // private marker in the middle
+}
+
+#[test]
+fn esc_sequences() {
+	assert_eq!(parse(&[b"\x1b7\x1b(B\x1b#8"]), vec!["esc 7", "esc (B", "esc #8"]);
+}
+
+#[test]
+fn strings() {
+	assert_eq!(parse(&[b"\x1b]0;title\x07"]), vec!["osc 0;title"]);
+	assert_eq!(parse(&[b"\x1b]2;ti", b"tle\x1b\\"]), vec!["osc 2;title", "esc \\"]);
+	assert_eq!(parse(&[b"\x1bP1$qm\x1b\\"]), vec!["dcs $[[1]]q m", "esc \\"]);
+	assert_eq!(parse(&[b"\x1b_apc\x1b\\a"]), vec!["esc \\", "\"a\""]); // APC is ignored
+}
@@ -0,0 +1,244 @@
+// color palette (16 ansi colors + rest of the xterm 256-color table + default colors) and theme loading
+
+use crate::config::{parse_toml, CONFIG_value};
+use std::fs;
+
+
+pub type RGB = (u8, u8, u8);
+
+#[derive(Clone)]
+pub struct PALETTE {
+	pub colors: [RGB; 256], // xterm 256-color table (0–15 come from the theme, the rest is the standard color cube and grayscale ramp unless the theme overrides it)
+	pub foreground: RGB, // default text color
+	pub background: RGB, // default background color
+	pub cursor: RGB,
+	pub selection: RGB, // background of selected text
+}
+impl PALETTE {
+
+	// builtin themes (name, 16 ansi colors, foreground, background, cursor, selection)
+	const BUILTIN: &'static [(&'static str, [RGB; 16], RGB, RGB, RGB, RGB)] = &[
+		("default", [
+			(0,0,0),       (205,0,0),     (0,205,0),     (205,205,0),
+			(0,0,238),     (205,0,205),   (0,205,205),   (229,229,229),
+			(127,127,127), (255,0,0),     (0,255,0),     (255,255,0),
+			(92,92,255),   (255,0,255),   (0,255,255),   (255,255,255),
+		], (229,229,229), (0,0,0), (229,229,229), (77,77,77)),
+		("light", [
+			(0,0,0),       (205,0,0),     (0,150,0),     (160,130,0),
+			(0,0,238),     (205,0,205),   (0,150,150),   (190,190,190),
+			(100,100,100), (230,0,0),     (0,190,0),     (190,160,0),
+			(70,70,255),   (230,0,230),   (0,180,180),   (255,255,255),
+		], (0,0,0), (255,255,255), (0,0,0), (180,213,255)),
+		("solarized-dark", [
+			(7,54,66),     (220,50,47),   (133,153,0),   (181,137,0),
+			(38,139,210),  (211,54,130),  (42,161,152),  (238,232,213),
+			(0,43,54),     (203,75,22),   (88,110,117),  (101,123,131),
+			(131,148,150), (108,113,196), (147,161,161), (253,246,227),
+		], (131,148,150), (0,43,54), (147,161,161), (7,54,66)),
+		("solarized-light", [
+			(7,54,66),     (220,50,47),   (133,153,0),   (181,137,0),
+			(38,139,210),  (211,54,130),  (42,161,152),  (238,232,213),
+			(0,43,54),     (203,75,22),   (88,110,117),  (101,123,131),
+			(131,148,150), (108,113,196), (147,161,161), (253,246,227),
+		], (101,123,131), (253,246,227), (88,110,117), (238,232,213)),
+		("dracula", [
+			(33,34,44),    (255,85,85),   (80,250,123),  (241,250,140),
+			(189,147,249), (255,121,198), (139,233,253), (248,248,242),
+			(98,114,164),  (255,110,110), (105,255,148), (255,255,165),
+			(214,172,255), (255,146,223), (164,255,255), (255,255,255),
+		], (248,248,242), (40,42,54), (248,248,242), (68,71,90)),
+		("gruvbox-dark", [
+			(40,40,40),    (204,36,29),   (152,151,26),  (215,153,33),
+			(69,133,136),  (177,98,134),  (104,157,106), (168,153,132),
+			(146,131,116), (251,73,52),   (184,187,38),  (250,189,47),
+			(131,165,152), (211,134,155), (142,192,124), (235,219,178),
+		], (235,219,178), (40,40,40), (235,219,178), (80,73,69)),
+	];
+
+	pub fn builtin(name: &str) -> Option<Self> {
+		let (_, ansi, foreground, background, cursor, selection) = PALETTE::BUILTIN.iter().find(|t| t.0 == name)?;
+		let mut colors = PALETTE::xterm256();
+		colors[..16].copy_from_slice(ansi);
+		Some(Self { colors, foreground: *foreground, background: *background, cursor: *cursor, selection: *selection })
+	}
+
+	pub fn builtin_names() -> Vec<&'static str> {
+		PALETTE::BUILTIN.iter().map(|t| t.0).collect()
+	}
+
+	// theme name or path to theme file
+	pub fn load(theme: &str) -> Result<Self, String> {
+
+		if let Some(palette) = PALETTE::builtin(theme) {
+			return Ok(palette);
+		}
+
+		let text = fs::read_to_string(theme).map_err(|e| format!("cant read theme '{}' ({}), builtin themes are: {}", theme, e, PALETTE::builtin_names().join(", ")))?;
+
+		// detect format
+		let result = if theme.ends_with(".itermcolors") || text.trim_start().starts_with("<?xml") { PALETTE::from_iterm(&text) }
+			else if theme.ends_with(".yaml") || theme.ends_with(".yml") { PALETTE::from_base16(&text) }
+			else { PALETTE::from_alacritty(&text) };
+
+		result.map_err(|e| format!("invalid theme '{}': {}", theme, e))
+	}
+
+	fn xterm256() -> [RGB; 256] {
+		let mut colors = [(0, 0, 0); 256];
+		colors[..16].copy_from_slice(&PALETTE::BUILTIN[0].1);
+
+		// 16–231: 6×6×6 color cube
+		for i in 16..232 {
+			let ci = (i - 16) as u8;
+			let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+			colors[i] = (level(ci / 36), level((ci % 36) / 6), level(ci % 6));
+		}
+
+		// 232–255: grayscale ramp
+		for i in 232..256 {
+			let gray = 8 + (i - 232) as u8 * 10;
+			colors[i] = (gray, gray, gray);
+		}
+
+		colors
+	}
+
+	fn parse_hex(color: &str) -> Option<RGB> { // '#rrggbb', '0xrrggbb' or 'rrggbb'
+		let hex = color.trim().trim_start_matches('#').trim_start_matches("0x");
+		if hex.len() != 6 { return None; }
+		let v = u32::from_str_radix(hex, 16).ok()?;
+		Some(((v >> 16) as u8, (v >> 8) as u8, v as u8))
+	}
+
+	fn from_alacritty(text: &str) -> Result<Self, String> { // alacritty toml ('[colors.primary]', '[colors.normal]', ...)
+
+		let mut palette = PALETTE::builtin("default").unwrap();
+		let names = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+		for (key, value) in parse_toml(text)? {
+
+			// old alacritty themes had no 'colors' table
+			let key = key.strip_prefix("colors.").unwrap_or(&key);
+
+			let color = || value.as_str().and_then(PALETTE::parse_hex).ok_or(format!("invalid color for '{}'", key));
+
+			match key {
+				"primary.foreground" => palette.foreground = color()?,
+				"primary.background" => palette.background = color()?,
+				"cursor.cursor" => palette.cursor = color()?,
+				"selection.background" => palette.selection = color()?,
+				"indexed_colors" => {
+					let CONFIG_value::Array(items) = &value else { return Err("'indexed_colors' has to be array".to_string()); };
+					for item in items {
+						let index = item.get("index").and_then(|i| i.as_integer()).filter(|i| (0..256).contains(i));
+						let color = item.get("color").and_then(|c| c.as_str()).and_then(PALETTE::parse_hex);
+						if let (Some(index), Some(color)) = (index, color) { palette.colors[index as usize] = color; }
+					}
+				}
+				_ => {
+					// normal and bright colors, everything else (dim colors, hints, ...) is ignored
+					for (offset, table) in [(0, "normal."), (8, "bright.")] {
+						if let Some(i) = key.strip_prefix(table).and_then(|name| names.iter().position(|n| *n == name)) {
+							palette.colors[offset + i] = color()?;
+						}
+					}
+				}
+			}
+		}
+
+		Ok(palette)
+	}
+
+	fn from_base16(text: &str) -> Result<Self, String> { // base16 scheme yaml ('base00: "181818"')
+
+		let mut base = [None; 16];
+		for line in text.lines() {
+			let Some((key, value)) = line.split_once(':') else { continue };
+			let Some(index) = key.trim().strip_prefix("base").and_then(|i| usize::from_str_radix(i, 16).ok()) else { continue };
+			if index < 16 { // quoted ('"#181818"') or bare value (where '#' starts a comment)
+				let value = value.trim();
+				let value = match value.chars().next() {
+					Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or(""),
+					_ => value.split_whitespace().next().filter(|v| !v.starts_with('#')).unwrap_or(""),
+				};
+				base[index] = PALETTE::parse_hex(value);
+			}
+		}
+		let base: Vec<RGB> = base.iter().enumerate().map(|(i, c)| c.ok_or(format!("missing color base{:02X}", i))).collect::<Result<_, _>>()?;
+
+		// standard base16-shell mapping
+		let mut palette = PALETTE::builtin("default").unwrap();
+		for (ansi, b) in [0x0, 0x8, 0xB, 0xA, 0xD, 0xE, 0xC, 0x5, 0x3, 0x8, 0xB, 0xA, 0xD, 0xE, 0xC, 0x7].iter().enumerate() {
+			palette.colors[ansi] = base[*b];
+		}
+		palette.foreground = base[0x5];
+		palette.background = base[0x0];
+		palette.cursor = base[0x5];
+		palette.selection = base[0x2];
+
+		Ok(palette)
+	}
+
+	fn from_iterm(text: &str) -> Result<Self, String> { // iterm2 .itermcolors (xml plist with float components)
+
+		let mut palette = PALETTE::builtin("default").unwrap();
+		let mut found = 0;
+
+		let mut rest = text;
+		while let Some(start) = rest.find("<key>") {
+			rest = &rest[start+5..];
+			let Some(end) = rest.find("</key>") else { break };
+			let name = rest[..end].to_string();
+			rest = &rest[end+6..];
+
+			// only keys followed by color dictionary are interesting
+			if !rest.trim_start().starts_with("<dict>") { continue; }
+			let Some(dict_end) = rest.find("</dict>") else { break };
+			let dict = &rest[..dict_end];
+
+			let component = |component: &str| -> Option<u8> {
+				let after = &dict[dict.find(&format!("<key>{} Component</key>", component))?..];
+				let value = &after[after.find("<real>")?+6..after.find("</real>")?];
+				Some((value.trim().parse::<f64>().ok()?.clamp(0.0, 1.0) * 255.0).round() as u8)
+			};
+			let Some(color) = component("Red").zip(component("Green")).zip(component("Blue")).map(|((r, g), b)| (r, g, b)) else { continue };
+
+			match name.as_str() {
+				"Foreground Color" => palette.foreground = color,
+				"Background Color" => palette.background = color,
+				"Cursor Color" => palette.cursor = color,
+				"Selection Color" => palette.selection = color,
+				_ => {
+					match name.strip_prefix("Ansi ").and_then(|n| n.strip_suffix(" Color")).and_then(|n| n.parse::<usize>().ok()) {
+						Some(i) if i < 16 => palette.colors[i] = color,
+						_ => continue,
+					}
+				}
+			}
+			found += 1;
+			rest = &rest[dict_end..];
+		}
+
+		if found == 0 { return Err("no colors found".to_string()); }
+		Ok(palette)
+	}
+
+	// css variables used by the terminal renderer
+	pub fn to_css(&self) -> String {
+		let rgb = |c: RGB| format!("rgb({},{},{})", c.0, c.1, c.2);
+		let mut css = format!(
+			"--termila_fg: {}; --termila_bg: {}; --termila_cursor: {}; --termila_selection: {};",
+			rgb(self.foreground), rgb(self.background), rgb(self.cursor), rgb(self.selection)
+		);
+		for (i, color) in self.colors.iter().enumerate() {
+			css.push_str(&format!(" --termila_color_{}: {};", i, rgb(*color)));
+		}
+		css
+	}
+
+}
+
+
+#[cfg(test)]
+mod tests;
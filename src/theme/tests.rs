@@ -0,0 +1,84 @@
+// theme loader unit tests (builtin palettes and alacritty, base16 and iterm2 files)
+
+use super::*;
+
+#[test]
+fn builtin() {
+	let palette = PALETTE::builtin("dracula").unwrap();
+	assert_eq!(palette.background, (40, 42, 54));
+	assert_eq!(palette.colors[1], (255, 85, 85));
+	assert_eq!(palette.colors[16], (0, 0, 0)); // color cube
+	assert_eq!(palette.colors[196], (255, 0, 0));
+	assert_eq!(palette.colors[232], (8, 8, 8)); // grayscale ramp
+	assert_eq!(palette.colors[255], (238, 238, 238));
+	assert!(PALETTE::builtin("nope").is_none());
+	assert!(PALETTE::load("nope").err().unwrap().contains("builtin themes are: default, light"));
+}
+
+#[test]
+fn hex() {
+	assert_eq!(PALETTE::parse_hex("#1a2B3c"), Some((0x1a, 0x2b, 0x3c)));
+	assert_eq!(PALETTE::parse_hex("0x1a2b3c"), Some((0x1a, 0x2b, 0x3c)));
+	assert_eq!(PALETTE::parse_hex(" 1a2b3c "), Some((0x1a, 0x2b, 0x3c)));
+	assert_eq!(PALETTE::parse_hex("#fff"), None);
+	assert_eq!(PALETTE::parse_hex("#gggggg"), None);
+}
+
+#[test]
+fn alacritty() {
+	let palette = PALETTE::from_alacritty("
+		[colors.primary]
+		foreground = '#d8d8d8'
+		background = '0x181818'
+		[colors.normal]
+		red = '#ab4642'
+		[colors.bright]
+		blue = '#7cafc2'
+		[colors.dim]
+		red = '#000001'
+		[colors]
+		indexed_colors = [{ index = 17, color = '#010203' }]
+	").unwrap();
+	assert_eq!((palette.foreground, palette.background), ((0xd8, 0xd8, 0xd8), (0x18, 0x18, 0x18)));
+	assert_eq!((palette.colors[1], palette.colors[12]), ((0xab, 0x46, 0x42), (0x7c, 0xaf, 0xc2)));
+	assert_eq!(palette.colors[17], (1, 2, 3));
+	assert_eq!(palette.colors[9], PALETTE::builtin("default").unwrap().colors[9]); // not in theme
+
+	// old themes without the 'colors' table
+	assert_eq!(PALETTE::from_alacritty("[primary]\nbackground = '#000010'").unwrap().background, (0, 0, 16));
+	assert_eq!(PALETTE::from_alacritty("[colors.normal]\nred = 'red'").err().unwrap(), "invalid color for 'normal.red'");
+}
+
+#[test]
+fn base16() {
+	let mut yaml = String::from("scheme: \"Test\"\nauthor: \"someone\"\n");
+	for i in 0..16 {
+		let value = match i % 4 {
+			0 => format!("\"{:02x}{:02x}{:02x}\"", i, i, i),
+			1 => format!("\"#{:02x}{:02x}{:02x}\"", i, i, i),
+			2 => format!("'{:02x}{:02x}{:02x}'", i, i, i),
+			_ => format!("{:02x}{:02x}{:02x} # comment", i, i, i),
+		};
+		yaml.push_str(&format!("base{:02X}: {}\n", i, value));
+	}
+	let palette = PALETTE::from_base16(&yaml).unwrap();
+	assert_eq!(palette.background, (0, 0, 0));
+	assert_eq!(palette.foreground, (5, 5, 5));
+	assert_eq!(palette.colors[1], (8, 8, 8));
+	assert_eq!(palette.colors[4], (13, 13, 13));
+	assert_eq!(palette.colors[15], (7, 7, 7));
+
+	// bare '#' starts a comment
+	let broken = yaml.replace("base0F: 0f0f0f # comment", "base0F: #0f0f0f");
+	assert_eq!(PALETTE::from_base16(&broken).err().unwrap(), "missing color base0F");
+}
+
+#[test]
+fn iterm() {
+	let color = |name: &str, r: f64| format!("<key>{}</key><dict><key>Blue Component</key><real>0</real><key>Green Component</key><real>0.5</real><key>Red Component</key><real>{}</real></dict>", name, r);
+	let plist = format!("<?xml version=\"1.0\"?><plist><dict>{}{}{}</dict></plist>", color("Ansi 1 Color", 1.0), color("Background Color", 0.0), color("Unknown", 1.0));
+	let palette = PALETTE::from_iterm(&plist).unwrap();
+	assert_eq!(palette.colors[1], (255, 128, 0));
+	assert_eq!(palette.background, (0, 128, 0));
+	assert_eq!(PALETTE::from_iterm("<plist></plist>").err().unwrap(), "no colors found");
+}